@@ -1,6 +1,7 @@
 use crate::{
+    config::Config,
     context::Context,
-    event::{Command, SendResult},
+    event::{Command, Dispatcher, SendResult},
     ui::Painter,
 };
 
@@ -11,28 +12,36 @@ use std::{error::Error, path::PathBuf};
 pub struct App {
     context: Context,
     painter: Painter,
-    current_file: PathBuf,
     fps: u64,
 }
 
 impl App {
     pub async fn new(file_path: PathBuf, fps: u64) -> crossterm::Result<App> {
-        let context = Context::new()?;
-        let painter = Painter::new(context.worker.clone())?;
+        // Fall back to defaults on a bad config, reporting why once drawn
+        let (config, config_error) = match Config::try_load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+
+        let context = Context::new(config, file_path)?;
+        let mut painter = Painter::new()?;
+        if let Some(e) = config_error {
+            painter.update(Command::Error(e.to_string()));
+        }
 
         Ok(App {
             context,
             painter,
-            current_file: file_path,
             fps,
         })
     }
 
     pub async fn run(&mut self) -> Result<(), Box<dyn Error>> {
-        let config = &self.context.config;
-        self.context
+        let context = &mut self.context;
+        context
             .cache
-            .populate_to_root(&self.current_file, config)?;
+            .populate_to_root(&context.path, &context.config)?;
+        context.load_preview();
 
         if let Err(e) = self.event_loop().await {
             eprintln!("{}", e)
@@ -59,12 +68,53 @@ impl App {
     }
 
     async fn update(&mut self) {
-        self.painter.update().await;
+        if !self.context.jobs.is_empty() {
+            for e in self.context.poll_jobs() {
+                self.painter.update(Command::Error(e.to_string()));
+            }
+        }
+        for e in self.context.poll_loads() {
+            self.painter.update(Command::Error(e.to_string()));
+        }
+        self.context.poll_watcher();
+
+        let worker = self.context.worker.lock().await;
+        let command = match worker.receive_command().await {
+            Ok(command) => command,
+            Err(_) => return,
+        };
+        drop(worker);
+
+        match self.context.dispatch(&command) {
+            Ok(()) => match command {
+                Command::CacheStats => {
+                    let stats = self.context.cache.stats();
+                    self.painter.update(Command::Debug(stats.to_string()))
+                }
+                command => self.painter.update(command),
+            },
+            Err(e) => self.painter.update(Command::Error(e.to_string())),
+        }
+
+        // Keep command line paths relative to where we are
+        let mut worker = self.context.worker.lock().await;
+        if worker.directory != self.context.path {
+            worker.directory = self.context.path.clone();
+        }
     }
 
     async fn render(&mut self) -> crossterm::Result<()> {
-        let cache = &self.context.cache;
-        self.painter.render(cache, &self.current_file).await
+        let context = &self.context;
+        let prompt = context.worker.lock().await.prompt().cloned();
+        self.painter
+            .render(
+                &context.cache,
+                &context.jobs,
+                &context.config,
+                prompt.as_ref(),
+                &context.path,
+            )
+            .await
     }
 
     async fn handle_event(&mut self) -> SendResult<bool, Command> {
@@ -1,29 +1,160 @@
-use serde::Deserialize;
-use std::{fs, path::PathBuf};
+use crate::{
+    event::Keybindings,
+    fs::{IconSet, MatchMode, SortMode},
+    ui::{Column, Theme},
+};
+use serde::{Deserialize, Deserializer};
+use std::{collections::HashMap, fmt::Display, fs, io, path::PathBuf};
 
-#[derive(Default, Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
+#[derive(Default, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub show_hidden: bool,
     pub show_icons: bool,
+    pub icons: IconConfig,
+    pub columns: ColumnConfig,
+    pub theme: Theme,
+    pub sort: SortConfig,
+    pub search: SearchConfig,
+    pub cache: CacheConfig,
+    pub preview: PreviewConfig,
+    pub keybindings: Keybindings,
+}
+
+// Icons shown beside entries, with overrides by file name or extension,
+// both ignoring case, or exact directory name
+#[derive(Default, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconConfig {
+    pub set: IconSet,
+    #[serde(deserialize_with = "lowercase_keys")]
+    pub names: HashMap<String, String>,
+    #[serde(deserialize_with = "lowercase_keys")]
+    pub extensions: HashMap<String, String>,
+    pub directories: HashMap<String, String>,
+}
+
+// Icon overrides are looked up by lowercase name, so keys are too
+fn lowercase_keys<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map = HashMap::<String, String>::deserialize(deserializer)?;
+    Ok(map
+        .into_iter()
+        .map(|(key, icon)| (key.to_lowercase(), icon))
+        .collect())
+}
+
+// Details shown beside entries in the current directory
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnConfig {
+    pub show: Vec<Column>,
+    // Show how long ago entries were modified, rather than when
+    pub relative_time: bool,
+    // A strftime style format for absolute times
+    pub time_format: String,
+}
+
+impl Default for ColumnConfig {
+    fn default() -> Self {
+        Self {
+            show: vec![Column::Size, Column::Modified],
+            relative_time: true,
+            time_format: "%Y-%m-%d %H:%M".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SortConfig {
+    pub mode: SortMode,
+    pub reverse: bool,
+    pub dirs_first: bool,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        Self {
+            mode: SortMode::default(),
+            reverse: false,
+            dirs_first: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub mode: MatchMode,
+    // Ignore case unless the pattern contains capitals
+    pub smart_case: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            mode: MatchMode::default(),
+            smart_case: true,
+        }
+    }
+}
+
+// Budget for directory listings kept in memory, where 0 is unlimited
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub max_directories: usize,
+    // Entries across all cached directories
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_directories: 128,
+            max_entries: 50_000,
+        }
+    }
+}
+
+// File previews, highlighted with one of syntect's built in themes
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreviewConfig {
+    pub highlight: bool,
+    pub syntax_theme: String,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            highlight: true,
+            syntax_theme: "base16-ocean.dark".to_string(),
+        }
+    }
 }
 
 impl Config {
     // Trys to load a config, returning default if none are found
-    pub fn try_load() -> Self {
+    // and an error if one exists but is invalid
+    pub fn try_load() -> Result<Self, ConfigError> {
         if let Some(home_dir) = home::home_dir() {
             let canonicalize = |partial| home_dir.clone().join(partial);
             let possible_partials = [".config/suha.toml", ".config/suha/config.toml"];
 
             for partial in possible_partials {
                 match Config::read(canonicalize(partial)) {
-                    Ok(v) => return v,
-                    Err(_) => continue,
+                    Ok(v) => return Ok(v),
+                    Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
                 }
             }
         };
 
-        Config::default()
+        Ok(Config::default())
     }
 
     fn read(path: PathBuf) -> Result<Config, ConfigError> {
@@ -38,6 +169,15 @@ pub enum ConfigError {
     Parse(toml::de::Error),
 }
 
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read config: {}", err),
+            ConfigError::Parse(err) => write!(f, "invalid config: {}", err),
+        }
+    }
+}
+
 impl From<std::io::Error> for ConfigError {
     fn from(err: std::io::Error) -> Self {
         ConfigError::Io(err)
@@ -49,3 +189,17 @@ impl From<toml::de::Error> for ConfigError {
         ConfigError::Parse(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_config_loads() {
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        assert_eq!(
+            config.icons.names.get("cargo.toml").map(String::as_str),
+            Some("📦")
+        );
+    }
+}
@@ -1,21 +1,47 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use tokio::sync::Mutex;
 
-use crate::{config::Config, event::Worker, fs::Cache};
+use crate::{
+    config::Config,
+    event::Worker,
+    fs::{Cache, Journal, Register, Watcher},
+    job::JobManager,
+};
 
 pub struct Context {
     pub config: Config,
     pub worker: Arc<Mutex<Worker>>,
     pub cache: Cache,
+    pub register: Register,
+    pub journal: Journal,
+    pub jobs: JobManager,
+    pub watcher: Watcher,
+    pub search: Option<Search>,
+    // The directory being browsed
+    pub path: PathBuf,
+}
+
+// The last search, and where the selection was when it began
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub pattern: String,
+    pub origin: Option<usize>,
 }
 
 impl Context {
-    pub fn new() -> crossterm::Result<Self> {
+    pub fn new(config: Config, path: PathBuf) -> crossterm::Result<Self> {
+        let worker = Worker::new(config.keybindings.clone(), path.clone());
         Ok(Self {
-            config: Config::try_load(),
-            worker: Arc::new(Mutex::new(Worker::new())),
+            config,
+            worker: Arc::new(Mutex::new(worker)),
             cache: Cache::new(),
+            register: Register::new(),
+            journal: Journal::new(),
+            jobs: JobManager::new(),
+            watcher: Watcher::new(),
+            search: None,
+            path,
         })
     }
 }
@@ -0,0 +1,485 @@
+use crate::{
+    context::{Context, Search},
+    event::{prompt, Command, DispatchError, DispatchResult, Dispatcher, Movement, PromptKind},
+    fs::{self, archive::ArchiveKind, Matcher, Operation, RegisterMode},
+    job::Task,
+};
+
+use std::{
+    collections::HashSet,
+    fs as std_fs,
+    path::{Path, PathBuf},
+};
+
+impl Dispatcher for Context {
+    fn dispatch(&mut self, command: &Command) -> DispatchResult<()> {
+        match command {
+            Command::Move(movement) => self.navigate(movement),
+            Command::Mark => {
+                if let Some(dir) = self.cache.get_mut(&self.path) {
+                    dir.toggle_flag();
+                    dir.select_next();
+                }
+                self.load_preview();
+                Ok(())
+            }
+            Command::Copy => self.yank(RegisterMode::Copy),
+            Command::Cut => self.yank(RegisterMode::Cut),
+            Command::Paste => self.paste(),
+            Command::Delete => self.delete(),
+            Command::Cancel => match self.jobs.cancel_last() {
+                true => Ok(()),
+                false => Err(DispatchError::NoJobs),
+            },
+            Command::Undo => self.undo(),
+            Command::Cd(path) => self.cd(path),
+            Command::Mkdir(name) => self.create(name, true),
+            Command::Touch(name) => self.create(name, false),
+            Command::Rename(name) => self.rename(name),
+            Command::Extract(name) => self.extract(name.as_deref()),
+            Command::Compress(name) => self.compress(name),
+            Command::Set(name, value) => self.set(name, *value),
+            Command::Sort(mode) => {
+                self.config.sort.mode = *mode;
+                self.reload_all()
+            }
+            Command::Prompt(PromptKind::Search) => {
+                // Searches start from here, and return here if cancelled
+                self.search = Some(Search {
+                    pattern: String::new(),
+                    origin: self.cache.get(&self.path).and_then(|dir| dir.index),
+                });
+                Ok(())
+            }
+            Command::Search(pattern) => self.search(pattern),
+            Command::SearchNext => self.search_next(false),
+            Command::SearchPrev => self.search_next(true),
+            Command::Filter(pattern) => self.filter(pattern),
+            Command::None
+            | Command::Exit
+            | Command::Prompt(_)
+            | Command::CacheStats
+            | Command::Debug(_)
+            | Command::Error(_) => Ok(()),
+        }
+    }
+}
+
+impl Context {
+    // Records finished jobs in the journal and refreshes what they touched,
+    // returning the failures
+    pub fn poll_jobs(&mut self) -> Vec<DispatchError> {
+        let mut errors = Vec::new();
+        for job in self.jobs.poll() {
+            let cancelled = job.was_cancelled();
+            self.journal.record(job.operations);
+
+            if let Task::Paste {
+                sources, cut: true, ..
+            } = &job.task
+            {
+                self.forget_moved(sources, job.result.is_ok());
+            }
+            match job.result {
+                Err(e) if !cancelled => errors.push(DispatchError::Job(job.task.to_string(), e)),
+                _ => {}
+            }
+            for path in job.task.affected() {
+                if let Err(e) = self.cache.reload(&path, &self.config) {
+                    errors.push(DispatchError::at(&path, e));
+                }
+            }
+        }
+
+        self.load_preview();
+        errors
+    }
+
+    // Reloads cached directories which have changed on disk, in the
+    // background. Ones which can no longer be read keep their old listing,
+    // so there's still something to show until we navigate away
+    pub fn poll_watcher(&mut self) {
+        let changed = self.watcher.changed(&self.cache);
+        if changed.is_empty() {
+            return;
+        }
+
+        for path in changed.iter() {
+            let _ = self.cache.reload(path, &self.config);
+        }
+        self.load_preview();
+    }
+
+    // Merges in entries from background loads, loading the preview once a
+    // directory turns up under the selection. Archives which can't be read
+    // are reported, leaving them if we'd gone in
+    pub fn poll_loads(&mut self) -> Vec<DispatchError> {
+        let (arrived, failed) = self.cache.poll_loads(&self.config);
+        if failed.is_empty() {
+            if arrived {
+                self.load_preview();
+            }
+            return Vec::new();
+        }
+
+        let cache = &self.cache;
+        if let Some(nearest) = self.path.ancestors().find(|a| cache.get(a).is_some()) {
+            let nearest = nearest.to_path_buf();
+            if let Some(dir) = self.cache.get_mut(&nearest) {
+                dir.select_path(&self.path);
+            }
+            self.path = nearest;
+        }
+        self.load_preview();
+        failed
+            .into_iter()
+            .map(|(path, e)| DispatchError::at(&path, e))
+            .collect()
+    }
+
+    fn navigate(&mut self, movement: &Movement) -> DispatchResult<()> {
+        if let Movement::In | Movement::Out = movement {
+            return Err(DispatchError::Unsupported(Command::Move(movement.clone())));
+        }
+
+        // Blame the directory being entered if loading fails
+        let target = match movement {
+            Movement::Left => self.path.parent().map(|p| p.to_path_buf()),
+            Movement::Right => self.selected_path(),
+            _ => None,
+        }
+        .unwrap_or_else(|| self.path.clone());
+
+        self.path = fs::navigate(&mut self.cache, &self.path, movement, &self.config)
+            .map_err(|e| DispatchError::at(&target, e))?;
+        self.load_preview();
+        Ok(())
+    }
+
+    // Captures the flagged entries, or the selected one, into the register
+    fn yank(&mut self, mode: RegisterMode) -> DispatchResult<()> {
+        if mode == RegisterMode::Cut {
+            self.check_writable()?;
+        }
+        if let Some(dir) = self.cache.get_mut(&self.path) {
+            let paths = dir.flagged_or_selected();
+            dir.clear_flags();
+            self.register.set(mode, paths);
+        }
+        Ok(())
+    }
+
+    fn paste(&mut self) -> DispatchResult<()> {
+        if self.register.is_empty() {
+            return Err(DispatchError::NothingToPaste);
+        }
+        self.check_writable()?;
+
+        // Cut entries are kept until the move succeeds, see `poll_jobs`
+        self.jobs.spawn(Task::Paste {
+            sources: self.register.paths.clone(),
+            dest: self.path.clone(),
+            cut: self.register.mode == RegisterMode::Cut,
+        });
+        Ok(())
+    }
+
+    // Cut entries are gone once moved, so they can only be pasted once. When
+    // a move fails or is cancelled, whatever didn't get moved is kept
+    fn forget_moved(&mut self, sources: &[PathBuf], succeeded: bool) {
+        if self.register.mode != RegisterMode::Cut {
+            return;
+        }
+        let sources: HashSet<&PathBuf> = sources.iter().collect();
+        self.register.paths.retain(|path| {
+            !sources.contains(path) || (!succeeded && path.symlink_metadata().is_ok())
+        });
+    }
+
+    // Moves the flagged entries, or the selected one, to the trash
+    fn delete(&mut self) -> DispatchResult<()> {
+        self.check_writable()?;
+        if let Some(dir) = self.cache.get_mut(&self.path) {
+            let paths = dir.flagged_or_selected();
+            dir.clear_flags();
+            if !paths.is_empty() {
+                self.jobs.spawn(Task::Trash(paths));
+            }
+        }
+        Ok(())
+    }
+
+    // Extracts the selected archive into the current directory, or a
+    // subdirectory of it
+    fn extract(&mut self, name: Option<&str>) -> DispatchResult<()> {
+        self.check_writable()?;
+        let archive = self.selected_path().ok_or(DispatchError::NothingSelected)?;
+        let supported = archive
+            .file_name()
+            .and_then(|name| ArchiveKind::from_name(&name.to_string_lossy()));
+        if supported.is_none() || !archive.is_file() {
+            return Err(DispatchError::NotAnArchive(archive));
+        }
+
+        let dest = match name {
+            Some(name) => self.path.join(name),
+            None => self.path.clone(),
+        };
+        self.jobs.spawn(Task::Extract { archive, dest });
+        Ok(())
+    }
+
+    // Compresses the flagged entries, or the selected one, into a new
+    // archive in the current directory
+    fn compress(&mut self, name: &str) -> DispatchResult<()> {
+        self.check_writable()?;
+        check_name(name)?;
+        let archive = self.path.join(name);
+        if ArchiveKind::from_name(name).is_none() {
+            return Err(DispatchError::NotAnArchive(archive));
+        }
+        if std_fs::symlink_metadata(&archive).is_ok() {
+            return Err(DispatchError::AlreadyExists(archive));
+        }
+
+        let dir = match self.cache.get_mut(&self.path) {
+            Some(dir) => dir,
+            None => return Err(DispatchError::NothingSelected),
+        };
+        let sources = dir.flagged_or_selected();
+        dir.clear_flags();
+        if sources.is_empty() {
+            return Err(DispatchError::NothingSelected);
+        }
+        self.jobs.spawn(Task::Compress { sources, archive });
+        Ok(())
+    }
+
+    fn undo(&mut self) -> DispatchResult<()> {
+        if self.journal.is_empty() {
+            return Err(DispatchError::NothingToUndo);
+        }
+
+        // Whatever was reverted before a failure still needs showing
+        let (affected, result) = self.journal.undo();
+        let mut reloaded = Ok(());
+        for path in affected.iter().chain(std::iter::once(&self.path)) {
+            if let Err(e) = self.cache.reload(path, &self.config) {
+                reloaded = reloaded.and(Err(DispatchError::at(path, e)));
+            }
+        }
+        self.load_preview();
+        result.map_err(DispatchError::Io)?;
+        reloaded
+    }
+
+    fn cd(&mut self, path: &str) -> DispatchResult<()> {
+        let path = prompt::resolve_path(path, &self.path);
+        let path = path
+            .canonicalize()
+            .map_err(|e| DispatchError::at(&path, e))?;
+        if !path.is_dir() {
+            return Err(DispatchError::NotADirectory(path));
+        }
+
+        self.cache
+            .populate_to_root(&path, &self.config)
+            .map_err(|e| DispatchError::at(&path, e))?;
+        self.path = path;
+        self.load_preview();
+        Ok(())
+    }
+
+    // Creates an empty directory or file in the current directory
+    fn create(&mut self, name: &str, directory: bool) -> DispatchResult<()> {
+        self.check_writable()?;
+        check_name(name)?;
+        let path = self.path.join(name);
+        let result = if directory {
+            std_fs::create_dir(&path).map(|_| Operation::Mkdir(path.clone()))
+        } else {
+            std_fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .map(|_| Operation::Create(path.clone()))
+        };
+        let operation = result.map_err(|e| DispatchError::at(&path, e))?;
+        self.journal.record(vec![operation]);
+
+        self.reload_and_select(&path)
+    }
+
+    fn rename(&mut self, name: &str) -> DispatchResult<()> {
+        self.check_writable()?;
+        check_name(name)?;
+        let from = self.selected_path().ok_or(DispatchError::NothingSelected)?;
+        let to = self.path.join(name);
+        if std_fs::symlink_metadata(&to).is_ok() {
+            return Err(DispatchError::AlreadyExists(to));
+        }
+
+        std_fs::rename(&from, &to).map_err(|e| DispatchError::at(&from, e))?;
+        self.journal.record(vec![Operation::Rename {
+            from,
+            to: to.clone(),
+        }]);
+
+        self.reload_and_select(&to)
+    }
+
+    // Changes a setting, reloading listings so they reflect it
+    fn set(&mut self, name: &str, value: Option<bool>) -> DispatchResult<()> {
+        let setting = match name {
+            "show_hidden" => &mut self.config.show_hidden,
+            "show_icons" => &mut self.config.show_icons,
+            "smart_case" => &mut self.config.search.smart_case,
+            "sort_reverse" => &mut self.config.sort.reverse,
+            "dirs_first" => &mut self.config.sort.dirs_first,
+            _ => {
+                return Err(DispatchError::Unsupported(Command::Set(
+                    name.to_string(),
+                    value,
+                )))
+            }
+        };
+        *setting = value.unwrap_or(!*setting);
+        self.reload_all()
+    }
+
+    // Reloads every listing, so they reflect changed settings
+    fn reload_all(&mut self) -> DispatchResult<()> {
+        self.path = self.cache.reload_all(&self.path, &self.config);
+        self.cache
+            .populate_to_root(&self.path, &self.config)
+            .map_err(|e| DispatchError::at(&self.path, e))?;
+        self.load_preview();
+        Ok(())
+    }
+
+    // Selects the first match at or after where the search began, returning
+    // there if nothing matches or the pattern is cleared
+    fn search(&mut self, pattern: &str) -> DispatchResult<()> {
+        let matcher = match pattern.is_empty() {
+            true => None,
+            false => Some(self.matcher(pattern)?),
+        };
+        let origin = match &self.search {
+            Some(search) => search.origin,
+            None => self.cache.get(&self.path).and_then(|dir| dir.index),
+        };
+        let dir = match self.cache.get_mut(&self.path) {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        let found = matcher.and_then(|m| dir.find(&m, origin.unwrap_or(0), false));
+        dir.index = found.or_else(|| origin.filter(|&i| i < dir.len()));
+        self.search = match pattern.is_empty() {
+            true => None,
+            false => Some(Search {
+                pattern: pattern.to_string(),
+                origin,
+            }),
+        };
+        self.load_preview();
+
+        match found {
+            None if !pattern.is_empty() => Err(DispatchError::NoMatches(pattern.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    // Selects the next match of the last search, wrapping around the listing
+    fn search_next(&mut self, reverse: bool) -> DispatchResult<()> {
+        let pattern = match &self.search {
+            Some(search) if !search.pattern.is_empty() => search.pattern.clone(),
+            _ => return Err(DispatchError::NoSearch),
+        };
+        let matcher = self.matcher(&pattern)?;
+        let dir = match self.cache.get_mut(&self.path) {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        let start = match (dir.index, reverse) {
+            (Some(i), false) => i + 1,
+            (Some(i), true) => i + dir.len() - 1,
+            (None, _) => 0,
+        };
+        match dir.find(&matcher, start, reverse) {
+            Some(i) => dir.index = Some(i),
+            None => return Err(DispatchError::NoMatches(pattern)),
+        }
+        self.load_preview();
+        Ok(())
+    }
+
+    // Hides entries in the current directory which don't match `pattern`
+    fn filter(&mut self, pattern: &str) -> DispatchResult<()> {
+        let filter = match pattern.is_empty() {
+            true => None,
+            false => Some(self.matcher(pattern)?),
+        };
+        if let Some(dir) = self.cache.get_mut(&self.path) {
+            dir.set_filter(filter);
+        }
+        self.load_preview();
+        Ok(())
+    }
+
+    fn matcher(&self, pattern: &str) -> DispatchResult<Matcher> {
+        let search = &self.config.search;
+        Ok(Matcher::new(pattern, search.mode, search.smart_case)?)
+    }
+
+    fn reload_and_select(&mut self, path: &Path) -> DispatchResult<()> {
+        let current = &self.path;
+        self.cache
+            .reload(current, &self.config)
+            .map_err(|e| DispatchError::at(current, e))?;
+        if let Some(dir) = self.cache.get_mut(current) {
+            dir.select_path(path);
+        }
+        self.load_preview();
+        Ok(())
+    }
+
+    // Listings inside archives can be browsed and copied from, but not
+    // changed
+    fn check_writable(&self) -> DispatchResult<()> {
+        match self.cache.archive_of(&self.path) {
+            Some(archive) => Err(DispatchError::ReadOnly(archive.to_path_buf())),
+            None => Ok(()),
+        }
+    }
+
+    fn selected_path(&self) -> Option<PathBuf> {
+        let entry = self.cache.get(&self.path)?.selected()?;
+        Some(entry.path.clone())
+    }
+
+    // Failures are left for the preview pane to report
+    pub fn load_preview(&mut self) {
+        let _ = fs::load_preview(&mut self.cache, &self.path, &self.config);
+        self.trim_cache();
+    }
+
+    // Keeps the directories being shown, cancelling loads of others and
+    // dropping them once over budget
+    fn trim_cache(&mut self) {
+        let preview = self.selected_path();
+        self.cache.cancel_loads(&self.path, preview.as_deref());
+        self.cache
+            .trim(&self.path, preview.as_deref(), &self.config.cache);
+    }
+}
+
+// New entries are named within the current directory, so names can't
+// reach outside it or be empty
+fn check_name(name: &str) -> DispatchResult<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(std::path::is_separator) {
+        return Err(DispatchError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
@@ -1,17 +1,40 @@
-use std::fmt::Display;
+use super::prompt::PromptKind;
+use crate::fs::SortMode;
+
+use std::{
+    fmt::Display,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone)]
 pub enum Command {
     None,
-    Exit,           // Unimplemented
-    Mark,           // Unimplemented
-    Copy,           // Unimplemented
-    Cut,            // Unimplemented
-    Paste,          // Unimplemented
-    Undo,           // Unimplemented
-    Move(Movement), // Unimplemented
-    Debug(String),  // Logs debug info
-    Error(String),  // Logs error info
+    Exit,
+    Mark,
+    Copy,
+    Cut,
+    Paste,
+    Delete,
+    Cancel, // Cancels the most recent job
+    Undo,
+    Move(Movement),
+    Prompt(PromptKind),        // Opens the command line, search or filter prompt
+    Search(String),            // Selects the first match, clearing the search when empty
+    SearchNext,                // Selects the next match of the last search
+    SearchPrev,                // Selects the previous match of the last search
+    Filter(String),            // Hides non-matching entries, clearing the filter when empty
+    Cd(String),                // Changes directory to a path, relative or absolute
+    Mkdir(String),             // Creates a directory in the current one
+    Touch(String),             // Creates a file in the current one
+    Rename(String),            // Renames the selected entry
+    Extract(Option<String>),   // Extracts the selected archive, into a subdirectory if named
+    Compress(String),          // Compresses the flagged entries into a new archive
+    Set(String, Option<bool>), // Sets a config option, toggling on `None`
+    Sort(SortMode),            // Changes the key listings are sorted by
+    CacheStats,                // Shows how the directory cache is doing
+    Debug(String),             // Logs debug info
+    Error(String),             // Logs error info
 }
 
 impl Display for Command {
@@ -23,8 +46,26 @@ impl Display for Command {
             Command::Copy => "Command(Copy)".to_string(),
             Command::Cut => "Command(Cut)".to_string(),
             Command::Paste => "Command(Paste)".to_string(),
+            Command::Delete => "Command(Delete)".to_string(),
+            Command::Cancel => "Command(Cancel)".to_string(),
             Command::Undo => "Command(Undo)".to_string(),
             Command::Move(direction) => format!("Command(Move({}))", direction),
+            Command::Prompt(kind) => format!("Command(Prompt({}))", kind),
+            Command::Search(pattern) => format!("Command(Search({}))", pattern),
+            Command::SearchNext => "Command(SearchNext)".to_string(),
+            Command::SearchPrev => "Command(SearchPrev)".to_string(),
+            Command::Filter(pattern) => format!("Command(Filter({}))", pattern),
+            Command::Cd(path) => format!("Command(Cd({}))", path),
+            Command::Mkdir(name) => format!("Command(Mkdir({}))", name),
+            Command::Touch(name) => format!("Command(Touch({}))", name),
+            Command::Rename(name) => format!("Command(Rename({}))", name),
+            Command::Extract(Some(name)) => format!("Command(Extract({}))", name),
+            Command::Extract(None) => "Command(Extract)".to_string(),
+            Command::Compress(name) => format!("Command(Compress({}))", name),
+            Command::Set(name, Some(value)) => format!("Command(Set({}, {}))", name, value),
+            Command::Set(name, None) => format!("Command(Set({}, toggle))", name),
+            Command::Sort(mode) => format!("Command(Sort({}))", mode),
+            Command::CacheStats => "Command(CacheStats)".to_string(),
             Command::Debug(message) => format!("Debug: {}", message),
             Command::Error(message) => format!("Error: {}", message),
         };
@@ -34,12 +75,14 @@ impl Display for Command {
 
 #[derive(Debug, Clone)]
 pub enum Movement {
-    Up,    // ↑
-    Down,  // ↓
-    Left,  // ←
-    Right, // →
-    In,    // Into file inspection mode
-    Out,   // Out of file inspection mode
+    Up,     // ↑
+    Down,   // ↓
+    Left,   // ←
+    Right,  // →
+    Top,    // First entry
+    Bottom, // Last entry
+    In,     // Into file inspection mode
+    Out,    // Out of file inspection mode
 }
 
 impl Display for Movement {
@@ -49,6 +92,8 @@ impl Display for Movement {
             Movement::Down => "Down",
             Movement::Left => "Left",
             Movement::Right => "Right",
+            Movement::Top => "Top",
+            Movement::Bottom => "Bottom",
             Movement::In => "In",
             Movement::Out => "Out",
         };
@@ -59,6 +104,68 @@ impl Display for Movement {
 #[derive(Debug)]
 pub enum DispatchError {
     Io(std::io::Error),
+    PermissionDenied(PathBuf),
+    NotFound(PathBuf),
+    AlreadyExists(PathBuf),
+    InvalidName(String), // Not a single path component
+    NotADirectory(PathBuf),
+    NotAnArchive(PathBuf),
+    ReadOnly(PathBuf), // Inside an archive, which can only be copied from
+    NothingSelected,
+    NothingToPaste,
+    NothingToUndo,
+    NoJobs,
+    NoSearch,
+    NoMatches(String),
+    InvalidPattern(regex::Error),
+    Unsupported(Command),
+    Job(String, std::io::Error), // A background job failed
+}
+
+impl DispatchError {
+    // Attaches the path an io error occurred at, for the common failures
+    pub fn at(path: &Path, err: std::io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::PermissionDenied => DispatchError::PermissionDenied(path.to_path_buf()),
+            ErrorKind::NotFound => DispatchError::NotFound(path.to_path_buf()),
+            ErrorKind::AlreadyExists => DispatchError::AlreadyExists(path.to_path_buf()),
+            _ => DispatchError::Io(err),
+        }
+    }
+}
+
+impl Display for DispatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DispatchError::Io(err) => write!(f, "{}", err),
+            DispatchError::PermissionDenied(path) => {
+                write!(f, "permission denied: {}", path.display())
+            }
+            DispatchError::NotFound(path) => write!(f, "not found: {}", path.display()),
+            DispatchError::AlreadyExists(path) => {
+                write!(f, "already exists: {}", path.display())
+            }
+            DispatchError::InvalidName(name) => write!(f, "invalid name: {:?}", name),
+            DispatchError::NotADirectory(path) => {
+                write!(f, "not a directory: {}", path.display())
+            }
+            DispatchError::NotAnArchive(path) => {
+                write!(f, "not a supported archive: {}", path.display())
+            }
+            DispatchError::ReadOnly(path) => write!(f, "read-only: {}", path.display()),
+            DispatchError::NothingSelected => write!(f, "nothing selected"),
+            DispatchError::NothingToPaste => write!(f, "nothing to paste"),
+            DispatchError::NothingToUndo => write!(f, "nothing to undo"),
+            DispatchError::NoJobs => write!(f, "no jobs running"),
+            DispatchError::NoSearch => write!(f, "no previous search"),
+            DispatchError::NoMatches(pattern) => write!(f, "no matches: {}", pattern),
+            DispatchError::InvalidPattern(err) => write!(f, "invalid pattern: {}", err),
+            DispatchError::Unsupported(command) => {
+                write!(f, "unsupported: {}", command.to_string().trim_matches('\r'))
+            }
+            DispatchError::Job(name, err) => write!(f, "{} failed: {}", name, err),
+        }
+    }
 }
 
 impl From<std::io::Error> for DispatchError {
@@ -67,8 +174,15 @@ impl From<std::io::Error> for DispatchError {
     }
 }
 
+impl From<regex::Error> for DispatchError {
+    fn from(err: regex::Error) -> Self {
+        DispatchError::InvalidPattern(err)
+    }
+}
+
 pub type DispatchResult<T> = Result<T, DispatchError>;
 
-pub trait Dispatcher<T: Copy + Sized> {
-    fn dispatch<K: Sized>(key: K) -> fn() -> DispatchResult<T>;
+// Applies commands to application state
+pub trait Dispatcher {
+    fn dispatch(&mut self, command: &Command) -> DispatchResult<()>;
 }
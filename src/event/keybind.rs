@@ -1,7 +1,353 @@
-enum Keybind {
-    Up,
-    Down,
-    Left,
-    Right,
-    Inspect,
+use super::{command::Movement, prompt::PromptKind, Command};
+use crate::fs::SortMode;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{de, Deserialize, Deserializer};
+
+use std::{collections::HashMap, fmt::Display};
+
+pub type KeySequence = Vec<KeyEvent>;
+
+// Names of the commands which can be bound to keys
+pub const COMMAND_NAMES: [&str; 29] = [
+    "none",
+    "exit",
+    "quit",
+    "mark",
+    "copy",
+    "cut",
+    "paste",
+    "delete",
+    "cancel",
+    "undo",
+    "extract",
+    "move_up",
+    "move_down",
+    "move_left",
+    "move_right",
+    "move_top",
+    "move_bottom",
+    "command_line",
+    "search",
+    "search_next",
+    "search_prev",
+    "filter",
+    "sort_natural",
+    "sort_size",
+    "sort_modified",
+    "sort_extension",
+    "sort_type",
+    "sort_reverse",
+    "cache_stats",
+];
+
+const DEFAULT_BINDINGS: [(&str, &str); 30] = [
+    ("<Esc>", "exit"),
+    (":", "command_line"),
+    ("/", "search"),
+    ("n", "search_next"),
+    ("N", "search_prev"),
+    ("f", "filter"),
+    ("on", "sort_natural"),
+    ("os", "sort_size"),
+    ("om", "sort_modified"),
+    ("oe", "sort_extension"),
+    ("ot", "sort_type"),
+    ("or", "sort_reverse"),
+    ("m", "mark"),
+    ("y", "copy"),
+    ("d", "cut"),
+    ("p", "paste"),
+    ("D", "delete"),
+    ("x", "cancel"),
+    ("u", "undo"),
+    ("h", "move_left"),
+    ("j", "move_down"),
+    ("k", "move_up"),
+    ("l", "move_right"),
+    ("<Left>", "move_left"),
+    ("<Down>", "move_down"),
+    ("<Up>", "move_up"),
+    ("<Right>", "move_right"),
+    ("gg", "move_top"),
+    ("G", "move_bottom"),
+    ("<Enter>", "move_right"),
+];
+
+// Maps key sequences, written in vim notation (eg. `gg`, `<C-d>`), to commands
+#[derive(Debug, Clone)]
+pub struct Keybindings(HashMap<KeySequence, Command>);
+
+impl Keybindings {
+    // Binds `command` to `keys`, replacing any existing binding
+    pub fn bind(&mut self, keys: &str, command: &str) -> Result<(), KeybindError> {
+        let sequence = parse_sequence(keys)?;
+        let command = parse_command(command)
+            .ok_or_else(|| KeybindError::UnknownCommand(keys.to_string(), command.to_string()))?;
+
+        match command {
+            Command::None => self.0.remove(&sequence),
+            command => self.0.insert(sequence, command),
+        };
+        Ok(())
+    }
+
+    pub fn get(&self, sequence: &[KeyEvent]) -> Option<&Command> {
+        self.0.get(sequence)
+    }
+
+    // Whether a longer binding starts with `sequence`
+    pub fn is_prefix(&self, sequence: &[KeyEvent]) -> bool {
+        self.0
+            .keys()
+            .any(|keys| keys.len() > sequence.len() && keys.starts_with(sequence))
+    }
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        let mut keybindings = Self(HashMap::new());
+        for (keys, command) in DEFAULT_BINDINGS {
+            keybindings.bind(keys, command).unwrap();
+        }
+        keybindings
+    }
+}
+
+// User bindings are layered over the defaults, binding "none" removes one
+impl<'de> Deserialize<'de> for Keybindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let table = HashMap::<String, String>::deserialize(deserializer)?;
+
+        let mut keybindings = Keybindings::default();
+        for (keys, command) in table.iter() {
+            keybindings.bind(keys, command).map_err(de::Error::custom)?;
+        }
+        Ok(keybindings)
+    }
+}
+
+// Drops shift from characters, as it is already reflected in their case
+pub fn normalize(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(_) => KeyEvent::new(key.code, key.modifiers - KeyModifiers::SHIFT),
+        _ => key,
+    }
+}
+
+pub fn parse_command(name: &str) -> Option<Command> {
+    let command = match name {
+        "none" => Command::None,
+        "exit" | "quit" => Command::Exit,
+        "mark" => Command::Mark,
+        "copy" => Command::Copy,
+        "cut" => Command::Cut,
+        "paste" => Command::Paste,
+        "delete" => Command::Delete,
+        "cancel" => Command::Cancel,
+        "undo" => Command::Undo,
+        "extract" => Command::Extract(None),
+        "move_up" => Command::Move(Movement::Up),
+        "move_down" => Command::Move(Movement::Down),
+        "move_left" => Command::Move(Movement::Left),
+        "move_right" => Command::Move(Movement::Right),
+        "move_top" => Command::Move(Movement::Top),
+        "move_bottom" => Command::Move(Movement::Bottom),
+        "command_line" => Command::Prompt(PromptKind::Command),
+        "search" => Command::Prompt(PromptKind::Search),
+        "search_next" => Command::SearchNext,
+        "search_prev" => Command::SearchPrev,
+        "filter" => Command::Prompt(PromptKind::Filter),
+        "sort_natural" => Command::Sort(SortMode::Natural),
+        "sort_size" => Command::Sort(SortMode::Size),
+        "sort_modified" => Command::Sort(SortMode::Modified),
+        "sort_extension" => Command::Sort(SortMode::Extension),
+        "sort_type" => Command::Sort(SortMode::Type),
+        "sort_reverse" => Command::Set("sort_reverse".to_string(), None),
+        "cache_stats" => Command::CacheStats,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn parse_sequence(keys: &str) -> Result<KeySequence, KeybindError> {
+    let invalid = || KeybindError::InvalidKey(keys.to_string());
+
+    let mut sequence = Vec::new();
+    let mut chars = keys.chars();
+    while let Some(c) = chars.next() {
+        let key = if c == '<' {
+            let mut closed = false;
+            let special: String = chars
+                .by_ref()
+                .take_while(|&c| {
+                    closed = c == '>';
+                    !closed
+                })
+                .collect();
+            if !closed {
+                return Err(invalid());
+            }
+            parse_special(&special).ok_or_else(invalid)?
+        } else {
+            KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+        };
+        sequence.push(normalize(key));
+    }
+
+    if sequence.is_empty() {
+        return Err(invalid());
+    }
+    Ok(sequence)
+}
+
+// Parses the inside of a `<...>` key, eg. `C-d`, `S-Tab` or `Esc`
+fn parse_special(special: &str) -> Option<KeyEvent> {
+    let mut parts: Vec<&str> = special.split('-').collect();
+    let name = match parts.pop()? {
+        // `<C-->` binds the minus key
+        "" if special.ends_with("--") => {
+            parts.pop();
+            "-"
+        }
+        name => name,
+    };
+
+    let mut modifiers = KeyModifiers::NONE;
+    for part in parts {
+        modifiers |= match part {
+            "C" => KeyModifiers::CONTROL,
+            "S" => KeyModifiers::SHIFT,
+            "A" | "M" => KeyModifiers::ALT,
+            _ => return None,
+        };
+    }
+
+    let code = match name.to_lowercase().as_str() {
+        "esc" => KeyCode::Esc,
+        "enter" | "cr" => KeyCode::Enter,
+        "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        lower if lower.starts_with('f') && lower.len() > 1 => KeyCode::F(lower[1..].parse().ok()?),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                    KeyCode::Char(c.to_ascii_uppercase())
+                }
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(KeyEvent::new(code, modifiers))
+}
+
+#[derive(Debug)]
+pub enum KeybindError {
+    InvalidKey(String),
+    UnknownCommand(String, String),
+}
+
+impl Display for KeybindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeybindError::InvalidKey(keys) => write!(f, "invalid key sequence \"{}\"", keys),
+            KeybindError::UnknownCommand(keys, command) => {
+                write!(f, "unknown command \"{}\" bound to \"{}\"", command, keys)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn char_key(c: char) -> KeyEvent {
+        key(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn parses_plain_characters() {
+        assert_eq!(parse_sequence("gg").unwrap(), vec![char_key('g'); 2]);
+        assert_eq!(parse_sequence("G").unwrap(), vec![char_key('G')]);
+    }
+
+    #[test]
+    fn parses_special_keys() {
+        assert_eq!(
+            parse_sequence("<C-d>").unwrap(),
+            vec![key(KeyCode::Char('d'), KeyModifiers::CONTROL)]
+        );
+        assert_eq!(
+            parse_sequence("<S-Tab>").unwrap(),
+            vec![key(KeyCode::BackTab, KeyModifiers::SHIFT)]
+        );
+        assert_eq!(
+            parse_sequence("<Esc><F12><lt>").unwrap(),
+            vec![
+                key(KeyCode::Esc, KeyModifiers::NONE),
+                key(KeyCode::F(12), KeyModifiers::NONE),
+                char_key('<'),
+            ]
+        );
+        assert_eq!(
+            parse_sequence("<C-->").unwrap(),
+            vec![key(KeyCode::Char('-'), KeyModifiers::CONTROL)]
+        );
+    }
+
+    #[test]
+    fn shift_is_folded_into_characters() {
+        assert_eq!(parse_sequence("<S-a>").unwrap(), vec![char_key('A')]);
+        assert_eq!(
+            parse_sequence("<M-S-x>").unwrap(),
+            vec![key(KeyCode::Char('X'), KeyModifiers::ALT)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_sequences() {
+        for keys in ["", "<C-d", "<X-d>", "<nope>", "<>"] {
+            assert!(parse_sequence(keys).is_err(), "{}", keys);
+        }
+    }
+
+    #[test]
+    fn binding_none_removes_a_default() {
+        let mut keybindings = Keybindings::default();
+        let gg = parse_sequence("gg").unwrap();
+        assert!(keybindings.is_prefix(&gg[..1]));
+
+        keybindings.bind("gg", "none").unwrap();
+        assert!(keybindings.get(&gg).is_none());
+        assert!(!keybindings.is_prefix(&gg[..1]));
+        assert!(keybindings.bind("gg", "fly").is_err());
+    }
+
+    #[test]
+    fn every_command_name_parses() {
+        for name in COMMAND_NAMES {
+            assert!(parse_command(name).is_some(), "{}", name);
+        }
+    }
 }
@@ -1,5 +1,9 @@
 mod command;
+mod keybind;
+pub mod prompt;
 mod worker;
 
 pub use command::*;
+pub use keybind::*;
+pub use prompt::{Prompt, PromptKind};
 pub use worker::*;
@@ -0,0 +1,453 @@
+use super::{keybind, Command};
+use crate::fs::{SortMode, SORT_MODES};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Commands which take arguments, on top of those usable in keybindings
+const PROMPT_COMMANDS: [&str; 12] = [
+    "cd", "mkdir", "touch", "rename", "extract", "compress", "set", "sort", "search", "filter",
+    "q", "quit",
+];
+const SETTINGS: [&str; 5] = [
+    "show_hidden",
+    "show_icons",
+    "smart_case",
+    "sort_reverse",
+    "dirs_first",
+];
+
+// What the prompt wants done after handling a key
+pub enum PromptAction {
+    Continue,
+    Cancel,
+    Submit(String),
+}
+
+// What a prompt's line is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    Command,
+    Search,
+    Filter,
+}
+
+impl PromptKind {
+    // Shown before the line being edited
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            PromptKind::Command => ":",
+            PromptKind::Search => "/",
+            PromptKind::Filter => "filter: ",
+        }
+    }
+}
+
+impl Display for PromptKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PromptKind::Command => "Command",
+            PromptKind::Search => "Search",
+            PromptKind::Filter => "Filter",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A single line editor for the command line and search prompts
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub buffer: String,
+    // Cursor position in chars
+    pub cursor: usize,
+    // Position while scrolling through history, along with the line being
+    // edited before scrolling began
+    history_index: Option<(usize, String)>,
+    // Candidates being cycled through by repeated tabs, and where they start
+    completion: Option<Completion>,
+}
+
+#[derive(Debug, Clone)]
+struct Completion {
+    start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind) -> Self {
+        Self {
+            kind,
+            buffer: String::new(),
+            cursor: 0,
+            history_index: None,
+            completion: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, history: &[String], cwd: &Path) -> PromptAction {
+        if key.code != KeyCode::Tab {
+            self.completion = None;
+        }
+
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return PromptAction::Cancel,
+            KeyCode::Enter => return PromptAction::Submit(self.buffer.clone()),
+            KeyCode::Backspace if self.buffer.is_empty() => return PromptAction::Cancel,
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.buffer.remove(self.byte_index(self.cursor));
+            }
+            KeyCode::Delete if self.cursor < self.len() => {
+                self.buffer.remove(self.byte_index(self.cursor));
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.len(),
+            KeyCode::Up => self.history_prev(history),
+            KeyCode::Down => self.history_next(history),
+            // Patterns aren't commands or paths, so there's nothing to complete
+            KeyCode::Tab if self.kind == PromptKind::Command => self.complete(cwd),
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.len(),
+            KeyCode::Char('u') if ctrl => {
+                self.buffer
+                    .replace_range(..self.byte_index(self.cursor), "");
+                self.cursor = 0;
+            }
+            KeyCode::Char('w') if ctrl => {
+                let start = self.word_start();
+                self.buffer
+                    .replace_range(self.byte_index(start)..self.byte_index(self.cursor), "");
+                self.cursor = start;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.buffer.insert(self.byte_index(self.cursor), c);
+                self.cursor += 1;
+            }
+            _ => {}
+        }
+
+        PromptAction::Continue
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.chars().count()
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.buffer
+            .char_indices()
+            .nth(char_index)
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| self.buffer.len())
+    }
+
+    // Start of the whitespace separated word before the cursor
+    fn word_start(&self) -> usize {
+        let chars: Vec<char> = self.buffer.chars().take(self.cursor).collect();
+        let trimmed = chars.iter().rev().skip_while(|c| c.is_whitespace()).count();
+        chars[..trimmed]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1)
+    }
+
+    // Start of the argument under the cursor, which may be empty
+    fn token_start(&self) -> usize {
+        self.buffer
+            .chars()
+            .take(self.cursor)
+            .collect::<Vec<char>>()
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1)
+    }
+
+    fn set_buffer(&mut self, buffer: String) {
+        self.cursor = buffer.chars().count();
+        self.buffer = buffer;
+    }
+
+    fn history_prev(&mut self, history: &[String]) {
+        let index = match &self.history_index {
+            Some((0, _)) => return,
+            Some((i, _)) => i - 1,
+            None if history.is_empty() => return,
+            None => history.len() - 1,
+        };
+
+        let editing = match self.history_index.take() {
+            Some((_, editing)) => editing,
+            None => self.buffer.clone(),
+        };
+        self.history_index = Some((index, editing));
+        self.set_buffer(history[index].clone());
+    }
+
+    fn history_next(&mut self, history: &[String]) {
+        match self.history_index.take() {
+            Some((i, editing)) if i + 1 >= history.len() => self.set_buffer(editing),
+            Some((i, editing)) => {
+                self.history_index = Some((i + 1, editing));
+                self.set_buffer(history[i + 1].clone());
+            }
+            None => {}
+        }
+    }
+
+    // Completes the word before the cursor, cycling on repeated tabs
+    fn complete(&mut self, cwd: &Path) {
+        if self.completion.is_none() {
+            let start = self.token_start();
+            let word: String = self
+                .buffer
+                .chars()
+                .skip(start)
+                .take(self.cursor - start)
+                .collect();
+            let before: String = self.buffer.chars().take(start).collect();
+
+            let candidates = match before.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [] => complete_command(&word),
+                ["set"] => complete_from(&SETTINGS, &word),
+                ["sort"] => {
+                    let modes: Vec<&str> = SORT_MODES.iter().map(|(name, _)| *name).collect();
+                    complete_from(&modes, &word)
+                }
+                _ => complete_path(&word, cwd),
+            };
+            if candidates.is_empty() {
+                return;
+            }
+
+            self.completion = Some(Completion {
+                start,
+                candidates,
+                index: 0,
+            });
+        } else if let Some(completion) = self.completion.as_mut() {
+            completion.index = (completion.index + 1) % completion.candidates.len();
+        }
+
+        if let Some(completion) = &self.completion {
+            let candidate = &completion.candidates[completion.index];
+            let start = self.byte_index(completion.start);
+            let end = self.byte_index(self.cursor);
+            self.buffer.replace_range(start..end, candidate);
+            self.cursor = completion.start + candidate.chars().count();
+        }
+    }
+}
+
+fn complete_command(word: &str) -> Vec<String> {
+    let mut names: Vec<&str> = keybind::COMMAND_NAMES.to_vec();
+    names.extend_from_slice(&PROMPT_COMMANDS);
+    complete_from(&names, word)
+}
+
+fn complete_from(names: &[&str], word: &str) -> Vec<String> {
+    let mut candidates: Vec<String> = names
+        .iter()
+        .filter(|name| name.starts_with(word))
+        .map(|name| name.to_string())
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+fn complete_path(word: &str, cwd: &Path) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+
+    let entries = match fs::read_dir(resolve_path(dir, cwd)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Only offer hidden entries when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, suffix))
+        })
+        .collect();
+    alphanumeric_sort::sort_str_slice(&mut candidates);
+    candidates
+}
+
+// Resolves a path typed by the user, expanding `~` and making it absolute
+pub fn resolve_path(path: &str, cwd: &Path) -> PathBuf {
+    let path = match (path.strip_prefix('~'), home::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    };
+    cwd.join(path)
+}
+
+// Parses a submitted line into a command
+pub fn parse_line(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let (name, arg) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+
+    let require_arg = |command: fn(String) -> Command| {
+        if arg.is_empty() {
+            Err(format!("{} requires an argument", name))
+        } else {
+            Ok(command(arg.to_string()))
+        }
+    };
+
+    match name {
+        "" => Ok(Command::None),
+        "q" | "quit" => Ok(Command::Exit),
+        "cd" if arg.is_empty() => Ok(Command::Cd("~".to_string())),
+        "cd" => Ok(Command::Cd(arg.to_string())),
+        "mkdir" => require_arg(Command::Mkdir),
+        "touch" => require_arg(Command::Touch),
+        "rename" => require_arg(Command::Rename),
+        "extract" if !arg.is_empty() => Ok(Command::Extract(Some(arg.to_string()))),
+        "compress" => require_arg(Command::Compress),
+        "set" => parse_setting(arg),
+        "sort" if arg.is_empty() => Err("sort requires an argument".to_string()),
+        "sort" => SortMode::from_name(arg)
+            .map(Command::Sort)
+            .ok_or_else(|| format!("unknown sort mode: {}", arg)),
+        "search" if arg.is_empty() => Ok(Command::Prompt(PromptKind::Search)),
+        "search" => Ok(Command::Search(arg.to_string())),
+        "filter" if arg.is_empty() => Ok(Command::Prompt(PromptKind::Filter)),
+        "filter" => Ok(Command::Filter(arg.to_string())),
+        name => match keybind::parse_command(name) {
+            Some(command) if arg.is_empty() => Ok(command),
+            Some(_) => Err(format!("{} takes no arguments", name)),
+            None => Err(format!("unknown command: {}", name)),
+        },
+    }
+}
+
+// Parses vim style settings, `name`, `noname` or `name!` to toggle
+fn parse_setting(arg: &str) -> Result<Command, String> {
+    let (name, value) = if let Some(name) = arg.strip_suffix('!') {
+        (name, None)
+    } else if let Some(name) = arg.strip_prefix("no") {
+        (name, Some(false))
+    } else {
+        (arg, Some(true))
+    };
+
+    if SETTINGS.contains(&name) {
+        Ok(Command::Set(name.to_string(), value))
+    } else {
+        Err(format!("unknown setting: {}", arg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_with_arguments() {
+        assert!(matches!(parse_line("  "), Ok(Command::None)));
+        assert!(matches!(parse_line("q"), Ok(Command::Exit)));
+        assert!(matches!(parse_line("cd"), Ok(Command::Cd(path)) if path == "~"));
+        assert!(
+            matches!(parse_line("mkdir  new dir "), Ok(Command::Mkdir(name)) if name == "new dir")
+        );
+        assert!(matches!(parse_line("extract"), Ok(Command::Extract(None))));
+        assert!(
+            matches!(parse_line("extract out"), Ok(Command::Extract(Some(name))) if name == "out")
+        );
+        assert!(matches!(
+            parse_line("sort size"),
+            Ok(Command::Sort(SortMode::Size))
+        ));
+        assert!(matches!(
+            parse_line("filter"),
+            Ok(Command::Prompt(PromptKind::Filter))
+        ));
+        assert!(
+            matches!(parse_line("search a b"), Ok(Command::Search(pattern)) if pattern == "a b")
+        );
+    }
+
+    #[test]
+    fn reports_bad_commands() {
+        assert_eq!(
+            parse_line("touch").unwrap_err(),
+            "touch requires an argument"
+        );
+        assert_eq!(
+            parse_line("sort sideways").unwrap_err(),
+            "unknown sort mode: sideways"
+        );
+        assert_eq!(
+            parse_line("paste now").unwrap_err(),
+            "paste takes no arguments"
+        );
+        assert_eq!(parse_line("fly").unwrap_err(), "unknown command: fly");
+    }
+
+    #[test]
+    fn parses_settings_like_vim() {
+        let setting = |line| match parse_line(line) {
+            Ok(Command::Set(name, value)) => Some((name, value)),
+            _ => None,
+        };
+        assert_eq!(
+            setting("set show_hidden"),
+            Some(("show_hidden".to_string(), Some(true)))
+        );
+        assert_eq!(
+            setting("set noshow_hidden"),
+            Some(("show_hidden".to_string(), Some(false)))
+        );
+        assert_eq!(
+            setting("set dirs_first!"),
+            Some(("dirs_first".to_string(), None))
+        );
+        assert_eq!(
+            parse_line("set nothing").unwrap_err(),
+            "unknown setting: nothing"
+        );
+    }
+
+    #[test]
+    fn completes_every_prompt_command() {
+        for name in PROMPT_COMMANDS {
+            let unknown = format!("unknown command: {}", name);
+            assert!(parse_line(name).err() != Some(unknown), "{}", name);
+            assert!(complete_command(name).iter().any(|c| c == name), "{}", name);
+        }
+        assert_eq!(complete_command("ex"), ["exit", "extract"]);
+    }
+
+    #[test]
+    fn resolves_relative_paths() {
+        let cwd = Path::new("/tmp/here");
+        assert_eq!(resolve_path("there", cwd), Path::new("/tmp/here/there"));
+        assert_eq!(resolve_path("/etc", cwd), Path::new("/etc"));
+    }
+}
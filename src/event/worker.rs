@@ -1,15 +1,17 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
 use crossbeam_channel::{Receiver, SendError, Sender, TryRecvError};
-use crossterm::cursor;
-use crossterm::event::{Event, EventStream, KeyCode};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent};
 use futures::{select, FutureExt, StreamExt};
 use futures_timer::Delay;
 use tokio::sync::Mutex;
 
-use super::command::Movement;
+use super::keybind::{self, KeySequence, Keybindings};
+use super::prompt::{self, Prompt, PromptAction, PromptKind};
 use super::Command;
 
 type EventChannel = (Sender<Event>, Receiver<Event>);
@@ -23,10 +25,19 @@ pub type TryReceiveResult<T> = Result<T, TryRecvError>;
 pub struct Worker {
     event_channel: EventChannel,
     pub command_channel: CommandChannel,
+    keybindings: Keybindings,
+    pending: KeySequence,
+    // A key to parse again after completing the binding before it
+    replay: Option<KeyEvent>,
+    // The command line or search prompt, while it is open
+    prompt: Option<Prompt>,
+    history: HashMap<PromptKind, Vec<String>>,
+    // Directory that paths on the command line are relative to
+    pub directory: PathBuf,
 }
 
 impl Worker {
-    pub fn new() -> Self {
+    pub fn new(keybindings: Keybindings, directory: PathBuf) -> Self {
         let event_channel = crossbeam_channel::unbounded();
         let command_channel = Arc::new(Mutex::new(crossbeam_channel::unbounded()));
 
@@ -44,11 +55,9 @@ impl Worker {
                     maybe_event = event => {
                         match maybe_event {
                             Some(Ok(event)) => {
-                                // Send key over worker channel
-                                event_sender.send(event).unwrap();
-
-                                // Stop listening if escape key is pressed
-                                if event == Event::Key(KeyCode::Esc.into()) {
+                                // Send key over worker channel, stopping
+                                // once nothing is left to receive it
+                                if event_sender.send(event).is_err() {
                                     break;
                                 }
                             },
@@ -63,52 +72,118 @@ impl Worker {
         Self {
             event_channel,
             command_channel,
+            keybindings,
+            pending: Vec::new(),
+            replay: None,
+            prompt: None,
+            history: HashMap::new(),
+            directory,
         }
     }
 
     // Parse events sent from worker listener
-    pub fn parse_event(&self) -> Option<Command> {
+    pub fn parse_event(&mut self) -> Option<Command> {
+        if let Some(key) = self.replay.take() {
+            return self.parse_key(key);
+        }
+
         // try to recieve an event, returning nothing on error
         match self.event_channel.1.try_recv() {
-            Ok(event) => match event {
-                Event::Key(key) => match key.code {
-                    //
-                    KeyCode::Esc => Some(Command::Exit),
-                    KeyCode::Char(char) => match char {
-                        'm' => Some(Command::Mark),
-                        'y' => Some(Command::Copy),
-                        'd' => Some(Command::Cut),
-                        'p' => Some(Command::Paste),
-                        'u' => Some(Command::Undo),
-
-                        'h' => Some(Command::Move(Movement::Left)),
-                        'j' => Some(Command::Move(Movement::Down)),
-                        'k' => Some(Command::Move(Movement::Up)),
-                        'l' => Some(Command::Move(Movement::Right)),
-
-                        'c' => {
-                            let body = format!("Cursor position: {:?}\r", cursor::position());
-                            Some(Command::Debug(body))
-                        }
+            Ok(Event::Key(key)) => self.parse_key(keybind::normalize(key)),
+            _ => None,
+        }
+    }
+
+    // Matches a key against the bindings, waiting for more keys while the
+    // pending sequence could still be completed
+    fn parse_key(&mut self, key: KeyEvent) -> Option<Command> {
+        if self.prompt.is_some() {
+            return self.parse_prompt_key(key);
+        }
+
+        self.pending.push(key);
+        if self.keybindings.is_prefix(&self.pending) {
+            return None;
+        }
+
+        if let Some(command) = self.keybindings.get(&self.pending) {
+            let command = command.clone();
+            self.pending.clear();
+            if let Command::Prompt(kind) = command {
+                self.prompt = Some(Prompt::new(kind));
+            }
+            return Some(command);
+        }
+
+        // The sequence is dead, fall back to the last key on its own
+        let sequence = std::mem::take(&mut self.pending);
+        if sequence.len() > 1 {
+            // A shorter binding may have been waiting on this key
+            if let Some(command) = self.keybindings.get(&sequence[..sequence.len() - 1]) {
+                self.replay = Some(key);
+                return Some(command.clone());
+            }
+            return self.parse_key(key);
+        }
+
+        let body = match key.code {
+            KeyCode::Char(char) => format!("\rChar({})\r", char),
+            _ => format!("\rEvent::{:?}\r", key),
+        };
+        Some(Command::Debug(body))
+    }
+
+    // Edits the open prompt. Searches and filters are sent as they are typed,
+    // while command lines are parsed into a command once submitted
+    fn parse_prompt_key(&mut self, key: KeyEvent) -> Option<Command> {
+        let prompt = self.prompt.as_mut()?;
+        let kind = prompt.kind;
+        let before = prompt.buffer.clone();
+        let history = self.history.entry(kind).or_default();
+
+        match prompt.handle_key(key, history, &self.directory) {
+            PromptAction::Continue if prompt.buffer == before => None,
+            PromptAction::Continue => match kind {
+                PromptKind::Command => None,
+                PromptKind::Search => Some(Command::Search(prompt.buffer.clone())),
+                PromptKind::Filter => Some(Command::Filter(prompt.buffer.clone())),
+            },
+            PromptAction::Cancel => {
+                self.prompt = None;
+                match kind {
+                    PromptKind::Command => Some(Command::None),
+                    PromptKind::Search => Some(Command::Search(String::new())),
+                    PromptKind::Filter => Some(Command::Filter(String::new())),
+                }
+            }
+            PromptAction::Submit(line) => {
+                self.prompt = None;
+                if !line.trim().is_empty() && history.last() != Some(&line) {
+                    history.push(line.clone());
+                }
 
-                        _ => {
-                            let body = format!("\rChar({})\r", char);
-                            Some(Command::Debug(body))
+                match kind {
+                    PromptKind::Command => match prompt::parse_line(&line) {
+                        Ok(Command::Prompt(kind)) => {
+                            self.prompt = Some(Prompt::new(kind));
+                            Some(Command::Prompt(kind))
                         }
+                        Ok(command) => Some(command),
+                        Err(message) => Some(Command::Error(message)),
                     },
-                    _ => {
-                        let body = format!("\rEvent::{:?}\r", key);
-                        Some(Command::Debug(body))
-                    }
-                },
-                _ => None,
-            },
-            Err(_) => None,
+                    PromptKind::Search => Some(Command::Search(line)),
+                    PromptKind::Filter => Some(Command::Filter(line)),
+                }
+            }
         }
     }
 
+    pub fn prompt(&self) -> Option<&Prompt> {
+        self.prompt.as_ref()
+    }
+
     // Handles an event, returing true if the program should exit
-    pub async fn handle_event(&self) -> SendResult<bool, Command> {
+    pub async fn handle_event(&mut self) -> SendResult<bool, Command> {
         if let Some(command) = self.parse_event() {
             match command {
                 Command::Exit => return Ok(true),
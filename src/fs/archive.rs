@@ -0,0 +1,730 @@
+use super::{
+    metadata::{FileType, Metadata},
+    operation::{self, Monitor},
+    sort::sort,
+    Directory, Entry,
+};
+use crate::config::Config;
+
+use flate2::{read::GzDecoder, write::GzEncoder};
+use xz2::{read::XzDecoder, write::XzEncoder};
+use zip::write::FileOptions;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+}
+
+impl ArchiveKind {
+    // Archives are recognised by their extension, as tarballs have no magic
+    // number of their own once compressed
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        let kind = if name.ends_with(".zip") {
+            Self::Zip
+        } else if name.ends_with(".tar") {
+            Self::Tar
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Self::TarGz
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Self::TarXz
+        } else {
+            return None;
+        };
+        Some(kind)
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(&path.file_name()?.to_string_lossy())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub path: PathBuf,
+    // Uncompressed size
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+// An archive's members arranged into directories, so it can be browsed
+// read-only as if it were one. Listings inside it are keyed by the
+// archive's path joined with the member's, eg. `release.tar.gz/bin/suha`
+#[derive(Debug, Clone)]
+pub struct Archive {
+    pub path: PathBuf,
+    // The archive file's metadata, which members inherit
+    metadata: Metadata,
+    // Members of each directory, by their path within the archive
+    tree: HashMap<PathBuf, Vec<Member>>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let kind = ArchiveKind::from_path(path).ok_or_else(|| not_an_archive(path))?;
+        let metadata = Metadata::from(path)?;
+
+        // Tarballs often leave out directories, so they're filled in from
+        // the paths of their contents
+        let mut tree: HashMap<PathBuf, Vec<Member>> = HashMap::new();
+        let mut dirs = HashSet::new();
+        tree.insert(PathBuf::new(), Vec::new());
+        for member in members(path, kind)? {
+            let path = normalize(&member.path);
+            let mut ancestors: Vec<&Path> = path.ancestors().skip(1).collect();
+            ancestors.pop();
+            for dir in ancestors.into_iter().rev() {
+                add_dir(&mut tree, &mut dirs, dir);
+            }
+
+            if member.is_dir {
+                add_dir(&mut tree, &mut dirs, &path);
+            } else if let Some(parent) = path.parent().map(Path::to_path_buf) {
+                let file = Member { path, ..member };
+                tree.entry(parent).or_default().push(file);
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            metadata,
+            tree,
+        })
+    }
+
+    pub fn modified(&self) -> SystemTime {
+        self.metadata.modified
+    }
+
+    // Whether the archive has changed on disk since it was read
+    pub fn is_stale(&self) -> bool {
+        match fs::metadata(&self.path).and_then(|m| m.modified()) {
+            Ok(modified) => modified > self.metadata.modified,
+            Err(_) => true,
+        }
+    }
+
+    // Lists the directory at `path`, which is the archive's path or one
+    // beneath it
+    pub fn directory(&self, path: &Path, config: &Config) -> io::Result<Directory> {
+        let inner = path.strip_prefix(&self.path).map_err(|_| not_found(path))?;
+        let members = self.tree.get(inner).ok_or_else(|| not_found(path))?;
+
+        let mut entries: Vec<Entry> = members
+            .iter()
+            .filter_map(|member| {
+                let name = member.path.file_name()?.to_string_lossy().to_string();
+                if !config.show_hidden && name.starts_with('.') {
+                    return None;
+                }
+                let file_type = match member.is_dir {
+                    true => FileType::Directory(self.tree.get(&member.path).map(Vec::len)),
+                    false => FileType::File,
+                };
+                let metadata = self.metadata.member(member.size, file_type);
+                let path = path.join(&name);
+                Some(Entry::new(name, path, metadata, config))
+            })
+            .collect();
+        sort(&mut entries, &config.sort);
+
+        Ok(Directory::archived(
+            path.to_path_buf(),
+            entries,
+            self.modified(),
+            self.path.clone(),
+        ))
+    }
+}
+
+fn add_dir(tree: &mut HashMap<PathBuf, Vec<Member>>, dirs: &mut HashSet<PathBuf>, dir: &Path) {
+    if !dirs.insert(dir.to_path_buf()) {
+        return;
+    }
+    tree.entry(dir.to_path_buf()).or_default();
+    if let Some(parent) = dir.parent() {
+        tree.entry(parent.to_path_buf()).or_default().push(Member {
+            path: dir.to_path_buf(),
+            size: 0,
+            is_dir: true,
+        });
+    }
+}
+
+// Lists the members of an archive without extracting them. Compressed
+// tarballs still have to be decompressed on the way through
+pub fn members(path: &Path, kind: ArchiveKind) -> io::Result<Vec<Member>> {
+    let file = fs::File::open(path)?;
+    match kind {
+        ArchiveKind::Zip => zip_members(file),
+        ArchiveKind::Tar => tar_members(file),
+        ArchiveKind::TarGz => tar_members(GzDecoder::new(file)),
+        ArchiveKind::TarXz => tar_members(XzDecoder::new(file)),
+    }
+}
+
+fn zip_members(file: fs::File) -> io::Result<Vec<Member>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut members = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        // Raw access reads the header without decompressing anything
+        let file = archive.by_index_raw(i)?;
+        members.push(Member {
+            path: PathBuf::from(file.name()),
+            size: file.size(),
+            is_dir: file.is_dir(),
+        });
+    }
+    Ok(members)
+}
+
+fn tar_members(reader: impl Read) -> io::Result<Vec<Member>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut members = Vec::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        members.push(Member {
+            path: entry.path()?.into_owned(),
+            size: header.size()?,
+            is_dir: header.entry_type().is_dir(),
+        });
+    }
+    Ok(members)
+}
+
+// Splits a path inside an archive into the archive's path and the member's
+// path within it. Paths which exist on disk aren't inside an archive
+pub fn locate(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    if fs::symlink_metadata(path).is_ok() {
+        return None;
+    }
+
+    // The first ancestor which exists has to be the archive
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| fs::symlink_metadata(ancestor).is_ok())?;
+    if !archive.is_file() || ArchiveKind::from_path(archive).is_none() {
+        return None;
+    }
+    let inner = path.strip_prefix(archive).ok()?;
+    Some((archive.to_path_buf(), inner.to_path_buf()))
+}
+
+// Total uncompressed size and number of files at `inner` in an archive
+pub fn measure(archive: &Path, inner: &Path) -> io::Result<(u64, usize)> {
+    let kind = ArchiveKind::from_path(archive).ok_or_else(|| not_an_archive(archive))?;
+    Ok(members(archive, kind)?
+        .iter()
+        .filter(|member| !member.is_dir && normalize(&member.path).starts_with(inner))
+        .fold((0, 0), |(bytes, files), member| {
+            (bytes + member.size, files + 1)
+        }))
+}
+
+// Reads up to `limit` bytes of the file at `inner` in an archive
+pub fn read(archive: &Path, inner: &Path, limit: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = None;
+    visit(archive, inner, &mut |_, reader| match reader {
+        Some(reader) => {
+            let mut buf = Vec::new();
+            reader.take(limit).read_to_end(&mut buf)?;
+            bytes = Some(buf);
+            Ok(false)
+        }
+        None => Ok(true),
+    })?;
+    bytes.ok_or_else(|| not_found(&archive.join(inner)))
+}
+
+// Extracts the file or directory at `inner` in an archive to `to`
+pub fn extract(
+    archive: &Path,
+    inner: &Path,
+    to: &Path,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    let found = visit(archive, inner, &mut |path, reader| {
+        let target = match path.strip_prefix(inner) {
+            Ok(rest) if rest.as_os_str().is_empty() => to.to_path_buf(),
+            Ok(rest) => to.join(rest),
+            Err(_) => return Ok(true),
+        };
+        write_member(&target, reader, monitor)?;
+        Ok(true)
+    })?;
+    match found {
+        true => Ok(()),
+        false => Err(not_found(&archive.join(inner))),
+    }
+}
+
+// Extracts a whole archive into `dest`, returning each of its top level
+// members along with where they were put. Those which would land on an
+// existing path are renamed, as pasting does, rather than merged into it
+pub fn extract_all(
+    archive: &Path,
+    dest: &Path,
+    monitor: &mut dyn Monitor,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut extracted: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let result = visit(archive, Path::new(""), &mut |path, reader| {
+        let mut components = path.components();
+        let top = match components.next() {
+            Some(top) => PathBuf::from(top.as_os_str()),
+            None => return Ok(true),
+        };
+        let root = match extracted.iter().find(|(member, _)| *member == top) {
+            Some((_, root)) => root.clone(),
+            None => {
+                let root = operation::unique_path(&dest.join(&top));
+                extracted.push((top, root.clone()));
+                root
+            }
+        };
+
+        let rest = components.as_path();
+        let target = match rest.as_os_str().is_empty() {
+            true => root,
+            false => root.join(rest),
+        };
+        write_member(&target, reader, monitor)?;
+        Ok(true)
+    });
+
+    // Nothing is left half extracted
+    if let Err(e) = result {
+        for (_, root) in extracted.iter() {
+            let _ = operation::remove_recursive(root);
+        }
+        return Err(e);
+    }
+    Ok(extracted)
+}
+
+fn write_member(
+    target: &Path,
+    reader: Option<&mut dyn Read>,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    let reader = match reader {
+        Some(reader) => reader,
+        None => return fs::create_dir_all(target),
+    };
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    operation::write_file(reader, target, monitor)?;
+    operation::advance(monitor, 0, 1)
+}
+
+// Members are passed with their contents, or none for directories
+type Visitor<'a> = dyn FnMut(&Path, Option<&mut dyn Read>) -> io::Result<bool> + 'a;
+
+// Calls `visit` with each member at or under `inner`, while it returns
+// true, returning whether any were found
+fn visit(archive: &Path, inner: &Path, visit: &mut Visitor) -> io::Result<bool> {
+    let kind = ArchiveKind::from_path(archive).ok_or_else(|| not_an_archive(archive))?;
+    let file = fs::File::open(archive)?;
+    match kind {
+        ArchiveKind::Zip => visit_zip(file, inner, visit),
+        ArchiveKind::Tar => visit_tar(file, inner, visit),
+        ArchiveKind::TarGz => visit_tar(GzDecoder::new(file), inner, visit),
+        ArchiveKind::TarXz => visit_tar(XzDecoder::new(file), inner, visit),
+    }
+}
+
+fn visit_zip(file: fs::File, inner: &Path, visit: &mut Visitor) -> io::Result<bool> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut found = false;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = normalize(Path::new(file.name()));
+        if !path.starts_with(inner) {
+            continue;
+        }
+        found = true;
+        let reader: Option<&mut dyn Read> = match file.is_dir() {
+            true => None,
+            false => Some(&mut file),
+        };
+        if !visit(&path, reader)? {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+fn visit_tar(reader: impl Read, inner: &Path, visit: &mut Visitor) -> io::Result<bool> {
+    let mut archive = tar::Archive::new(reader);
+    let mut found = false;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = normalize(&entry.path()?);
+        let entry_type = entry.header().entry_type();
+        // Links and devices aren't extracted
+        if !(entry_type.is_file() || entry_type.is_dir()) || !path.starts_with(inner) {
+            continue;
+        }
+        found = true;
+        let reader: Option<&mut dyn Read> = match entry_type.is_dir() {
+            true => None,
+            false => Some(&mut entry),
+        };
+        if !visit(&path, reader)? {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+// Writes `sources` into a new archive at `to`, in the format its name asks
+// for
+pub fn create(sources: &[PathBuf], to: &Path, monitor: &mut dyn Monitor) -> io::Result<()> {
+    let kind = ArchiveKind::from_path(to).ok_or_else(|| not_an_archive(to))?;
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)?;
+    match kind {
+        ArchiveKind::Zip => {
+            let mut writer = zip::ZipWriter::new(file);
+            for source in sources {
+                add_zip(&mut writer, source, &file_name(source)?, monitor)?;
+            }
+            writer.finish()?;
+        }
+        ArchiveKind::Tar => {
+            create_tar(file, sources, monitor)?;
+        }
+        ArchiveKind::TarGz => {
+            let encoder = GzEncoder::new(file, flate2::Compression::default());
+            create_tar(encoder, sources, monitor)?.finish()?;
+        }
+        ArchiveKind::TarXz => {
+            create_tar(XzEncoder::new(file, 6), sources, monitor)?.finish()?;
+        }
+    }
+    Ok(())
+}
+
+fn create_tar<W: Write>(
+    writer: W,
+    sources: &[PathBuf],
+    monitor: &mut dyn Monitor,
+) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    for source in sources {
+        add_tar(
+            &mut builder,
+            source,
+            Path::new(&file_name(source)?),
+            monitor,
+        )?;
+    }
+    builder.into_inner()
+}
+
+fn add_tar<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &Path,
+    name: &Path,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        builder.append_dir(name, path)?;
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            add_tar(
+                builder,
+                &entry.path(),
+                &name.join(entry.file_name()),
+                monitor,
+            )?;
+        }
+        return Ok(());
+    }
+
+    if metadata.is_file() {
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&metadata);
+        let mut reader = Tracked {
+            inner: fs::File::open(path)?,
+            monitor,
+        };
+        builder.append_data(&mut header, name, &mut reader)?;
+        operation::advance(reader.monitor, 0, 1)
+    } else {
+        builder.append_path_with_name(path, name)?;
+        operation::advance(monitor, 0, 1)
+    }
+}
+
+fn add_zip(
+    writer: &mut zip::ZipWriter<fs::File>,
+    path: &Path,
+    name: &str,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    #[allow(unused_mut)]
+    let mut options = FileOptions::default();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        options = options.unix_permissions(metadata.permissions().mode() & 0o7777);
+    }
+
+    if metadata.is_dir() {
+        writer.add_directory(name, options)?;
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let child = format!("{}/{}", name, entry.file_name().to_string_lossy());
+            add_zip(writer, &entry.path(), &child, monitor)?;
+        }
+        return Ok(());
+    }
+
+    if metadata.file_type().is_symlink() {
+        let target = fs::read_link(path)?;
+        writer.add_symlink(name, target.to_string_lossy(), options)?;
+    } else {
+        writer.start_file(name, options)?;
+        let mut reader = Tracked {
+            inner: fs::File::open(path)?,
+            monitor,
+        };
+        io::copy(&mut reader, writer)?;
+    }
+    operation::advance(monitor, 0, 1)
+}
+
+// Reports progress as a file is read into an archive
+struct Tracked<'a, R> {
+    inner: R,
+    monitor: &'a mut dyn Monitor,
+}
+
+impl<R: Read> Read for Tracked<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        operation::advance(self.monitor, n as u64, 0)?;
+        Ok(n)
+    }
+}
+
+fn file_name(path: &Path) -> io::Result<String> {
+    match path.file_name() {
+        Some(name) => Ok(name.to_string_lossy().to_string()),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot archive {}", path.display()),
+        )),
+    }
+}
+
+// Keeps only the plain components of a member's path, so `./a` and `/a`
+// are both `a`, and nothing can point outside the archive
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+fn not_an_archive(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("not a supported archive: {}", path.display()),
+    )
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("not in the archive: {}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{
+        operation::{self, Monitor, Unmonitored},
+        Operation,
+    };
+
+    // Cancels once a set number of bytes have been written
+    struct CancelAfter(u64);
+
+    impl Monitor for CancelAfter {
+        fn advance(&mut self, bytes: u64, _: usize) -> bool {
+            self.0 = self.0.saturating_sub(bytes);
+            self.0 > 0
+        }
+    }
+
+    fn cancel_compress(name: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("data");
+        fs::write(&source, vec![7; 4 * 1024 * 1024]).unwrap();
+        let to = dir.path().join(name);
+
+        let mut done = Vec::new();
+        let err = operation::compress(&[source], &to, &mut done, &mut CancelAfter(64 * 1024))
+            .unwrap_err();
+
+        assert!(operation::is_cancelled(&err));
+        assert!(done.is_empty());
+        assert!(!to.exists());
+    }
+
+    #[test]
+    fn cancelling_zip_compress_stops() {
+        cancel_compress("data.zip");
+    }
+
+    #[test]
+    fn cancelling_tar_compress_stops() {
+        cancel_compress("data.tar.gz");
+    }
+
+    // Every path under `root` with the contents of files, `None` for
+    // directories
+    fn snapshot(root: &Path) -> Vec<(PathBuf, Option<Vec<u8>>)> {
+        let mut found = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(root).unwrap().to_path_buf();
+                if path.is_dir() {
+                    pending.push(path);
+                    found.push((relative, None));
+                } else {
+                    found.push((relative, Some(fs::read(&path).unwrap())));
+                }
+            }
+        }
+        found.sort();
+        found
+    }
+
+    // A directory tree and a loose file to compress, in `dir/src`
+    fn sources(dir: &Path) -> Vec<PathBuf> {
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("tree/sub")).unwrap();
+        fs::create_dir(src.join("tree/empty")).unwrap();
+        fs::write(src.join("tree/a.txt"), "a").unwrap();
+        fs::write(src.join("tree/sub/b.bin"), (0..=255).collect::<Vec<u8>>()).unwrap();
+        fs::write(src.join("top.txt"), "top").unwrap();
+        vec![src.join("tree"), src.join("top.txt")]
+    }
+
+    fn round_trip(name: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = sources(dir.path());
+        let archive = dir.path().join(name);
+        let dest = dir.path().join("dest");
+
+        let mut done = Vec::new();
+        operation::compress(&sources, &archive, &mut done, &mut Unmonitored).unwrap();
+        operation::extract(&archive, &dest, &mut done, &mut Unmonitored).unwrap();
+
+        assert_eq!(
+            snapshot(&dest),
+            snapshot(&dir.path().join("src")),
+            "{}",
+            name
+        );
+        assert_eq!(done.len(), 2);
+    }
+
+    #[test]
+    fn zip_round_trips() {
+        round_trip("data.zip");
+    }
+
+    #[test]
+    fn tar_round_trips() {
+        round_trip("data.tar");
+    }
+
+    #[test]
+    fn tar_gz_round_trips() {
+        round_trip("data.tar.gz");
+    }
+
+    #[test]
+    fn tar_xz_round_trips() {
+        round_trip("data.tar.xz");
+    }
+
+    #[test]
+    fn extracting_beside_existing_entries_keeps_them() {
+        for name in ["data.zip", "data.tar.gz"] {
+            let dir = tempfile::tempdir().unwrap();
+            let sources = sources(dir.path());
+            let archive = dir.path().join(name);
+            let mut done = Vec::new();
+            operation::compress(&sources, &archive, &mut done, &mut Unmonitored).unwrap();
+
+            // Extracting into the sources' own directory collides with both
+            let src = dir.path().join("src");
+            let before = snapshot(&src);
+            let mut done = Vec::new();
+            operation::extract(&archive, &src, &mut done, &mut Unmonitored).unwrap();
+
+            let after = snapshot(&src);
+            for entry in before.iter() {
+                assert!(after.contains(entry), "{} lost {:?}", name, entry.0);
+            }
+            assert_eq!(fs::read_to_string(src.join("top_1.txt")).unwrap(), "top");
+            assert_eq!(fs::read_to_string(src.join("tree_1/a.txt")).unwrap(), "a");
+            assert_eq!(after.len(), before.len() * 2);
+
+            // Each collision is recorded under its new name, so undo only
+            // removes what was extracted
+            let mut extracted: Vec<&Path> = done
+                .iter()
+                .map(|operation| match operation {
+                    Operation::Copy { to, .. } => to.as_path(),
+                    other => panic!("unexpected {:?}", other),
+                })
+                .collect();
+            extracted.sort();
+            assert_eq!(extracted, vec![src.join("top_1.txt"), src.join("tree_1")]);
+        }
+    }
+
+    #[test]
+    fn compressing_over_an_existing_file_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = sources(dir.path());
+        let archive = dir.path().join("data.zip");
+        fs::write(&archive, "precious").unwrap();
+
+        let mut done = Vec::new();
+        let err = operation::compress(&sources, &archive, &mut done, &mut Unmonitored).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&archive).unwrap(), "precious");
+        assert!(done.is_empty());
+    }
+}
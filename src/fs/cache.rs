@@ -1,75 +1,407 @@
-use super::{Directory, Entry};
-use crate::config::Config;
+use super::{
+    archive::Archive,
+    loader::{LoadEvent, Loader},
+    Directory, Entry,
+};
+use crate::config::{CacheConfig, Config};
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{hash_map, HashMap, HashSet},
     fmt::Display,
     io,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-// Note: we could consider a priority queue for dropping unused directories.
-// Depth could be checked, where the root and any directories up to a depth
-// of 2 won't be dropped, as they will likely be repopulated.
-
+// Directories are evicted least recently used first once the cache outgrows
+// its budget. The current path and its ancestors are never evicted, as every
+// pane would need them again straight away
 #[derive(Debug, Clone)]
-pub struct Cache(HashMap<PathBuf, Directory>);
+pub struct Cache {
+    inner: HashMap<PathBuf, Directory>,
+    // When each directory was last loaded, by a clock which ticks per load
+    last_used: HashMap<PathBuf, u64>,
+    clock: u64,
+    hits: usize,
+    misses: usize,
+    evictions: usize,
+    loader: Loader,
+    // Indexes of archives being browsed, by their path
+    archives: HashMap<PathBuf, Archive>,
+    // Archives whose indexes are being read, with the id of the load and
+    // when the archive was modified
+    opening: HashMap<PathBuf, (usize, SystemTime)>,
+}
+
+// How well the cache is doing, for debugging
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub directories: usize,
+    pub entries: usize,
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+}
 
 impl Cache {
     pub fn new() -> Self {
-        Self(HashMap::<PathBuf, Directory>::new())
+        Self {
+            inner: HashMap::new(),
+            last_used: HashMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+            loader: Loader::new(),
+            archives: HashMap::new(),
+            opening: HashMap::new(),
+        }
     }
 
+    // Loads every directory from `path` up to the root, selecting the one
+    // below in each
     pub fn populate_to_root(&mut self, path: &Path, config: &Config) -> io::Result<()> {
         let mut prev: Option<&Path> = None;
         for curr in path.ancestors() {
-            match self.as_mut().entry(curr.to_path_buf()) {
-                hash_map::Entry::Occupied(mut entry) => {
-                    let dir = entry.get_mut();
-                    dir.reload(config)?;
-                    if let Some(ancestor) = prev.as_ref() {
-                        if let Some(i) = find_index_in(&dir.inner, ancestor) {
-                            dir.index = Some(i);
-                        }
+            let dir = self.load(curr, config)?;
+            if let Some(ancestor) = prev {
+                dir.select_path(ancestor);
+            }
+            prev = Some(curr);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, k: &Path) -> Option<&Directory> {
+        self.as_ref().get(k)
+    }
+
+    pub fn get_mut(&mut self, k: &Path) -> Option<&mut Directory> {
+        self.as_mut().get_mut(k)
+    }
+
+    // Gets a directory, starting to load it in the background if it isn't
+    // present, or reloading it if it has changed on disk. Directories inside
+    // an opened archive are listed from its index instead, and left loading
+    // while the index is read
+    pub fn load(&mut self, path: &Path, config: &Config) -> io::Result<&mut Directory> {
+        self.touch(path);
+        let archives = &self.archives;
+        let archive = path.ancestors().find_map(|a| archives.get(a));
+        let opening = &self.opening;
+        let opening = path.ancestors().find_map(|a| opening.get_key_value(a));
+        match self.inner.entry(path.to_path_buf()) {
+            hash_map::Entry::Occupied(entry) => {
+                self.hits += 1;
+                let dir = entry.into_mut();
+                // Archives are refreshed by reloading, once the watcher
+                // notices them change
+                if dir.archive.is_none() && !dir.is_loading() && dir.modified() {
+                    let (id, modified) = self.loader.restart(path, config)?;
+                    dir.restart(id, modified);
+                }
+                Ok(dir)
+            }
+            hash_map::Entry::Vacant(entry) => {
+                self.misses += 1;
+                let dir = match (archive, opening) {
+                    (Some(archive), _) => archive.directory(path, config)?,
+                    (None, Some((archive, (id, modified)))) => {
+                        let mut dir = Directory::loading(path.to_path_buf(), *modified, *id);
+                        dir.archive = Some(archive.clone());
+                        dir
                     }
-                    prev = Some(curr);
+                    (None, None) => self.loader.spawn(path, config)?,
+                };
+                Ok(entry.insert(dir))
+            }
+        }
+    }
+
+    // Starts reading an archive's index in the background so it can be
+    // browsed, unless it is already open and unchanged, or being read
+    pub fn open_archive(&mut self, path: &Path) -> io::Result<()> {
+        if self.opening.contains_key(path) {
+            return Ok(());
+        }
+        if let Some(false) = self.archives.get(path).map(Archive::is_stale) {
+            return Ok(());
+        }
+        let opened = self.loader.open_archive(path)?;
+        self.opening.insert(path.to_path_buf(), opened);
+        Ok(())
+    }
+
+    // The path of the archive `path` is inside, if any, opened or still
+    // being read
+    pub fn archive_of(&self, path: &Path) -> Option<&Path> {
+        path.ancestors().find_map(|a| {
+            let archive = match self.archives.get_key_value(a) {
+                Some((archive, _)) => archive,
+                None => self.opening.get_key_value(a)?.0,
+            };
+            Some(archive.as_path())
+        })
+    }
+
+    // Reloads a directory in the background if it is cached, showing the
+    // old listing until it is done. One still loading is started over, so
+    // it picks up any change in settings
+    pub fn reload(&mut self, path: &Path, config: &Config) -> io::Result<()> {
+        let dir = match self.inner.get_mut(path) {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        if let Some(archive) = dir.archive.clone() {
+            return self.reload_archived(path, &archive, config);
+        }
+
+        if let Some(id) = dir.loading {
+            self.loader.cancel(id);
+        }
+        let (id, modified) = self.loader.restart(path, config)?;
+        dir.restart(id, modified);
+        Ok(())
+    }
+
+    // Lists a directory inside an archive again. If the archive has changed
+    // its index is read again in the background, and every listing inside it
+    // refreshed once that's done
+    fn reload_archived(&mut self, path: &Path, archive: &Path, config: &Config) -> io::Result<()> {
+        if self.archives.get(archive).is_none_or(Archive::is_stale) {
+            return self.open_archive(archive);
+        }
+
+        let index = &self.archives[archive];
+        if let Some(dir) = self.inner.get_mut(path) {
+            dir.relist(index.directory(path, config)?);
+        }
+        Ok(())
+    }
+
+    // Takes in a freshly read archive index, listing everything cached
+    // inside it from the index. Directories the archive no longer holds are
+    // dropped
+    fn opened(&mut self, archive: Archive, config: &Config) {
+        let paths: Vec<PathBuf> = self
+            .inner
+            .keys()
+            .filter(|k| k.starts_with(&archive.path))
+            .cloned()
+            .collect();
+        for path in paths {
+            match archive.directory(&path, config) {
+                Ok(listed) => {
+                    if let Some(dir) = self.inner.get_mut(&path) {
+                        dir.relist(listed);
+                    }
+                }
+                Err(_) => {
+                    self.evict(&path);
                 }
-                hash_map::Entry::Vacant(entry) => {
-                    let mut dir = Directory::new(curr.to_path_buf().clone(), config)?;
-                    if let Some(ancestor) = prev.as_ref() {
-                        if let Some(i) = find_index_in(&dir.inner, ancestor) {
-                            dir.index = Some(i);
-                        }
+            }
+        }
+        self.archives.insert(archive.path.clone(), archive);
+    }
+
+    // Reloads every cached directory, dropping any which can no longer be
+    // read. Returns the nearest of `path` and its ancestors which could be,
+    // as the place to carry on browsing from
+    pub fn reload_all(&mut self, path: &Path, config: &Config) -> PathBuf {
+        let paths: Vec<PathBuf> = self.inner.keys().cloned().collect();
+        let failed: HashSet<PathBuf> = paths
+            .into_iter()
+            .filter(|path| self.reload(path, config).is_err())
+            .collect();
+
+        let nearest = path
+            .ancestors()
+            .find(|ancestor| !failed.contains(*ancestor))
+            .unwrap_or(path);
+        for path in failed.iter() {
+            if !nearest.starts_with(path) {
+                self.evict(path);
+            }
+        }
+        nearest.to_path_buf()
+    }
+
+    // Merges in entries, counts and archive indexes from background loads,
+    // returning whether any arrived along with archives which couldn't be
+    // read. Subdirectories are counted once a listing is complete
+    pub fn poll_loads(&mut self, config: &Config) -> (bool, Vec<(PathBuf, io::Error)>) {
+        let events = self.loader.poll();
+        let arrived = !events.is_empty();
+
+        // Batches are merged together first, as each merge is linear in the
+        // size of the listing
+        let mut batches: HashMap<(usize, PathBuf), Vec<Entry>> = HashMap::new();
+        let mut counts: HashMap<(usize, PathBuf), Vec<(PathBuf, usize)>> = HashMap::new();
+        let mut finished = Vec::new();
+        let mut archives = Vec::new();
+        for event in events {
+            match event {
+                LoadEvent::Entries(id, path, entries) => {
+                    batches.entry((id, path)).or_default().extend(entries)
+                }
+                LoadEvent::Counts(id, path, batch) => {
+                    counts.entry((id, path)).or_default().extend(batch)
+                }
+                LoadEvent::Finished(id, path) => finished.push((id, path)),
+                LoadEvent::Archive(id, path, archive) => archives.push((id, path, archive)),
+            }
+        }
+
+        for ((id, path), entries) in batches {
+            match self.inner.get_mut(&path) {
+                Some(dir) if dir.loading == Some(id) => dir.extend(entries, &config.sort),
+                // Nothing wants these any more
+                _ => self.loader.cancel(id),
+            }
+        }
+        for ((id, path), counts) in counts {
+            match self.inner.get_mut(&path) {
+                Some(dir) if dir.counting == Some(id) => dir.set_counts(counts, &config.sort),
+                _ => self.loader.cancel(id),
+            }
+        }
+        for (id, path) in finished {
+            if let Some(dir) = self.inner.get_mut(&path) {
+                if dir.loading == Some(id) {
+                    dir.finish(&config.sort);
+                    self.loader.count(dir);
+                } else if dir.counting == Some(id) {
+                    dir.counting = None;
+                }
+            }
+        }
+
+        let mut failed = Vec::new();
+        for (id, path, archive) in archives {
+            if !matches!(self.opening.get(&path), Some((opening, _)) if *opening == id) {
+                continue;
+            }
+            self.opening.remove(&path);
+            match archive {
+                Ok(archive) => self.opened(archive, config),
+                Err(e) => {
+                    // Listings waiting on the index have nothing to show,
+                    // while older ones are kept
+                    let waiting: Vec<PathBuf> = self
+                        .inner
+                        .iter()
+                        .filter(|(k, dir)| k.starts_with(&path) && dir.loading == Some(id))
+                        .map(|(k, _)| k.clone())
+                        .collect();
+                    for key in waiting {
+                        self.evict(&key);
                     }
-                    entry.insert(dir);
-                    prev = Some(curr);
+                    failed.push((path, e));
                 }
             }
         }
-        Ok(())
+        (arrived, failed)
     }
 
-    pub fn get(&self, k: &Path) -> Option<&Directory> {
-        self.as_ref().get(k)
+    // Cancels loads of directories which are no longer shown, dropping their
+    // partial listings so they're read in full next time
+    pub fn cancel_loads(&mut self, path: &Path, preview: Option<&Path>) {
+        let stale: Vec<PathBuf> = self
+            .inner
+            .iter()
+            .filter(|(k, dir)| {
+                dir.is_loading() && !path.starts_with(k) && Some(k.as_path()) != preview
+            })
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in stale {
+            self.evict(&key);
+        }
     }
 
     pub fn set(&mut self, key: PathBuf, value: Directory) -> Option<Directory> {
+        self.touch(&key);
         self.as_mut().insert(key, value)
     }
 
     pub fn clear(&mut self) {
-        self.0.clear()
+        self.inner.clear();
+        self.last_used.clear();
+        self.archives.clear();
+        self.opening.clear();
+    }
+
+    // Evicts the least recently used directories until the cache is within
+    // budget, keeping `path`, its ancestors and `preview`
+    pub fn trim(&mut self, path: &Path, preview: Option<&Path>, config: &CacheConfig) {
+        let mut directories = self.inner.len();
+        let mut entries: usize = self.inner.values().map(Directory::len).sum();
+        let over_budget = |directories: usize, entries: usize| {
+            (config.max_directories > 0 && directories > config.max_directories)
+                || (config.max_entries > 0 && entries > config.max_entries)
+        };
+        if !over_budget(directories, entries) {
+            return;
+        }
+
+        let mut candidates: Vec<(u64, PathBuf)> = self
+            .inner
+            .keys()
+            .filter(|k| !path.starts_with(k) && Some(k.as_path()) != preview)
+            .map(|k| (self.last_used.get(k).copied().unwrap_or(0), k.clone()))
+            .collect();
+        candidates.sort();
+
+        for (_, key) in candidates {
+            if !over_budget(directories, entries) {
+                break;
+            }
+            if let Some(dir) = self.evict(&key) {
+                directories -= 1;
+                entries -= dir.len();
+                self.evictions += 1;
+            }
+        }
+
+        // Archives are closed once nothing inside them is cached
+        let inner = &self.inner;
+        self.archives
+            .retain(|archive, _| inner.keys().any(|k| k.starts_with(archive)));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            directories: self.inner.len(),
+            entries: self.inner.values().map(Directory::len).sum(),
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+
+    fn evict(&mut self, path: &Path) -> Option<Directory> {
+        self.last_used.remove(path);
+        let dir = self.inner.remove(path)?;
+        for id in dir.loading.iter().chain(dir.counting.iter()) {
+            self.loader.cancel(*id);
+        }
+        Some(dir)
+    }
+
+    fn touch(&mut self, path: &Path) {
+        self.clock += 1;
+        self.last_used.insert(path.to_path_buf(), self.clock);
     }
 }
 
 impl AsRef<HashMap<PathBuf, Directory>> for Cache {
     fn as_ref(&self) -> &HashMap<PathBuf, Directory> {
-        &self.0
+        &self.inner
     }
 }
 
 impl AsMut<HashMap<PathBuf, Directory>> for Cache {
     fn as_mut(&mut self) -> &mut HashMap<PathBuf, Directory> {
-        &mut self.0
+        &mut self.inner
     }
 }
 
@@ -83,12 +415,12 @@ impl Display for Cache {
     }
 }
 
-fn find_index_in(entries: &[Entry], path: &Path) -> Option<usize> {
-    entries.iter().enumerate().find_map(|(i, dir)| {
-        if dir.path.as_path() == path {
-            Some(i)
-        } else {
-            None
-        }
-    })
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cache: {} directories, {} entries, {} hits, {} misses, {} evicted",
+            self.directories, self.entries, self.hits, self.misses, self.evictions
+        )
+    }
 }
@@ -1,9 +1,15 @@
-use super::{Entry, Metadata};
-use crate::config::Config;
+use super::{
+    metadata::FileType,
+    sort::{merge, sort},
+    Entry, Matcher, SortMode,
+};
+use crate::config::{Config, SortConfig};
 use std::{
+    collections::HashMap,
     fmt::Display,
     fs, io, path,
     slice::{Iter, IterMut},
+    time::SystemTime,
 };
 
 #[derive(Debug, Clone)]
@@ -11,57 +17,248 @@ pub struct Directory {
     pub path: path::PathBuf,
     pub inner: Vec<Entry>,
     pub index: Option<usize>,
-    pub metadata: Metadata,
+    // When the directory last changed as of reading it
+    pub modified: SystemTime,
+    // Hides entries which don't match from the listing
+    pub filter: Option<Matcher>,
+    // Entries hidden by the filter, along with where they belong in the full
+    // listing
+    hidden: Vec<(usize, Entry)>,
+    // Id of the background load filling in the listing, while it runs
+    pub loading: Option<usize>,
+    // Id of the background pass counting subdirectories' entries
+    pub counting: Option<usize>,
+    // An entry to select once it has been loaded
+    pending: Option<path::PathBuf>,
+    // A fresh listing being read in the background, along with when the
+    // directory was modified. The old listing stays on show until it's done
+    staged: Option<(SystemTime, Vec<Entry>)>,
+    // The archive a read-only listing was read from
+    pub archive: Option<path::PathBuf>,
 }
 
 impl Directory {
-    pub fn new(path: path::PathBuf, config: &Config) -> io::Result<Self> {
-        let inner = read_dir_list(path.as_path(), config)?;
-        let index = if inner.is_empty() { None } else { Some(0) };
-        let metadata = Metadata::from(&path)?;
+    // An empty listing for the background load `id` to fill in
+    pub fn loading(path: path::PathBuf, modified: SystemTime, id: usize) -> Self {
+        Self {
+            path,
+            inner: Vec::new(),
+            index: None,
+            modified,
+            filter: None,
+            hidden: Vec::new(),
+            loading: Some(id),
+            counting: None,
+            pending: None,
+            staged: None,
+            archive: None,
+        }
+    }
 
-        Ok(Self {
+    // A listing of a directory inside `archive`
+    pub fn archived(
+        path: path::PathBuf,
+        inner: Vec<Entry>,
+        modified: SystemTime,
+        archive: path::PathBuf,
+    ) -> Self {
+        let index = if inner.is_empty() { None } else { Some(0) };
+        Self {
             path,
             inner,
             index,
-            metadata,
-        })
+            modified,
+            filter: None,
+            hidden: Vec::new(),
+            loading: None,
+            counting: None,
+            pending: None,
+            staged: None,
+            archive: Some(archive),
+        }
     }
 
-    pub fn reload(&mut self, config: &Config) -> io::Result<()> {
-        let inner = read_dir_list(&self.path, config)?;
-        let inner_len = inner.len();
-        let index: Option<usize> = if inner_len == 0 {
-            None
-        } else {
-            match self.index {
-                Some(i) if i >= inner_len => Some(inner_len - 1),
-                Some(i) => {
-                    let entry = &self.inner[i];
-                    inner
-                        .iter()
-                        .enumerate()
-                        .find(|(_, e)| e.name == entry.name)
-                        .map(|(i, _)| i)
-                        .or(Some(i))
+    // Takes over a fresh listing of a directory inside an archive, keeping
+    // the filter and selection
+    pub fn relist(&mut self, mut listed: Directory) {
+        listed.set_filter(self.filter.clone());
+        let selected = match self.pending.take() {
+            Some(path) => Some(path),
+            None => self.selected().map(|e| e.path.clone()),
+        };
+        if let Some(path) = selected {
+            listed.select_path(&path);
+        }
+        *self = listed;
+    }
+
+    // Starts reading the listing again, as the background load `id`
+    pub fn restart(&mut self, id: usize, modified: SystemTime) {
+        self.loading = Some(id);
+        self.staged = Some((modified, Vec::new()));
+    }
+
+    // Swaps in a fresh listing, keeping the selection
+    fn replace(&mut self, mut inner: Vec<Entry>, modified: SystemTime) {
+        let selected = self.selected().map(|e| e.name.clone());
+        self.unfilter();
+        // Keep flags on entries which still exist, and their counts until
+        // they're counted again
+        let old: HashMap<&path::Path, &Entry> =
+            self.iter().map(|e| (e.path.as_path(), e)).collect();
+        for entry in inner.iter_mut() {
+            if let Some(old) = old.get(entry.path.as_path()) {
+                entry.flagged = old.flagged;
+                if let (FileType::Directory(count), FileType::Directory(None)) =
+                    (&old.metadata.file_type, &entry.metadata.file_type)
+                {
+                    entry.metadata.file_type = FileType::Directory(*count);
                 }
-                None => Some(0),
             }
-        };
+        }
 
-        let metadata = Metadata::from(&self.path)?;
-        self.metadata = metadata;
+        self.modified = modified;
         self.inner = inner;
-        self.index = index;
+        self.apply_filter();
+        self.reselect(selected.as_deref());
+    }
+
+    // Merges a batch of entries from the background load into the listing,
+    // or sets them aside while reloading
+    pub fn extend(&mut self, mut entries: Vec<Entry>, config: &SortConfig) {
+        if let Some((_, staged)) = self.staged.as_mut() {
+            staged.append(&mut entries);
+            return;
+        }
+
+        let selected = self.selected().map(|e| e.name.clone());
+        self.unfilter();
+        sort(&mut entries, config);
+        self.inner = merge(std::mem::take(&mut self.inner), entries, config);
+        self.apply_filter();
+        self.reselect(selected.as_deref());
+
+        if let Some(path) = self.pending.take() {
+            self.select_path(&path);
+        }
+    }
+
+    // Fills in the entry counts of subdirectories. Listings sorted by size
+    // are sorted again, as directories are sized by their counts
+    pub fn set_counts(&mut self, counts: Vec<(path::PathBuf, usize)>, config: &SortConfig) {
+        let counts: HashMap<path::PathBuf, usize> = counts.into_iter().collect();
+        let entries = self
+            .inner
+            .iter_mut()
+            .chain(self.hidden.iter_mut().map(|(_, e)| e));
+        for entry in entries {
+            if let Some(count) = counts.get(&entry.path) {
+                entry.metadata.file_type = FileType::Directory(Some(*count));
+            }
+        }
+
+        if config.mode == SortMode::Size {
+            let selected = self.selected().map(|e| e.name.clone());
+            self.unfilter();
+            sort(&mut self.inner, config);
+            self.apply_filter();
+            self.reselect(selected.as_deref());
+        }
+    }
+
+    // Paths of the subdirectories listed, including any hidden by the filter
+    pub fn subdirectories(&self) -> Vec<path::PathBuf> {
+        self.iter()
+            .chain(self.hidden.iter().map(|(_, e)| e))
+            .filter(|e| e.metadata.is_dir())
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    // Marks the listing as complete, swapping in a reloaded one
+    pub fn finish(&mut self, config: &SortConfig) {
+        self.loading = None;
+        if let Some((modified, mut entries)) = self.staged.take() {
+            sort(&mut entries, config);
+            self.replace(entries, modified);
+        }
+        if let Some(path) = self.pending.take() {
+            self.select_path(&path);
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
+    // Replaces the filter, without reading the directory again
+    pub fn set_filter(&mut self, filter: Option<Matcher>) {
+        let selected = self.selected().map(|e| e.name.clone());
+        self.unfilter();
+        self.filter = filter;
+        self.apply_filter();
+        self.reselect(selected.as_deref());
+    }
+
+    fn apply_filter(&mut self) {
+        let matcher = match &self.filter {
+            Some(matcher) => matcher,
+            None => return,
+        };
+
+        let (visible, hidden): (Vec<_>, Vec<_>) = std::mem::take(&mut self.inner)
+            .into_iter()
+            .enumerate()
+            .partition(|(_, e)| matcher.is_match(&e.name));
+        self.inner = visible.into_iter().map(|(_, e)| e).collect();
+        self.hidden = hidden;
+    }
+
+    // Puts hidden entries back in their place, in ascending order so that
+    // each position is valid once the ones before it are restored
+    fn unfilter(&mut self) {
+        for (i, entry) in self.hidden.drain(..) {
+            let i = i.min(self.inner.len());
+            self.inner.insert(i, entry);
+        }
+    }
+
+    // Selects the entry named `name`, otherwise keeping the index in bounds
+    fn reselect(&mut self, name: Option<&str>) {
+        let len = self.len();
+        self.index = if len == 0 {
+            None
+        } else {
+            name.and_then(|name| self.iter().position(|e| e.name == name))
+                .or_else(|| self.index.map(|i| i.min(len - 1)))
+                .or(Some(0))
+        };
+    }
 
-        Ok(())
+    // Finds the next entry matching `matcher`, starting at `start` and
+    // wrapping around the listing
+    pub fn find(&self, matcher: &Matcher, start: usize, reverse: bool) -> Option<usize> {
+        let len = self.len();
+        (0..len)
+            .map(|offset| match reverse {
+                false => (start + offset) % len,
+                true => (start % len + len - offset) % len,
+            })
+            .find(|&i| matcher.is_match(&self.inner[i].name))
     }
 
-    pub fn iter(&self) -> Iter<Entry> {
+    pub fn iter(&self) -> Iter<'_, Entry> {
         self.inner.iter()
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<Entry> {
+    // At most `len` entries, from `start` on
+    pub fn window(&self, start: usize, len: usize) -> &[Entry] {
+        let start = start.min(self.inner.len());
+        let end = (start + len).min(self.inner.len());
+        &self.inner[start..end]
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, Entry> {
         self.inner.iter_mut()
     }
 
@@ -73,11 +270,92 @@ impl Directory {
         self.inner.is_empty()
     }
 
+    pub fn selected(&self) -> Option<&Entry> {
+        self.index.and_then(|i| self.inner.get(i))
+    }
+
+    pub fn select_next(&mut self) {
+        self.pending = None;
+        if let Some(i) = self.index {
+            if i + 1 < self.len() {
+                self.index = Some(i + 1);
+            }
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.pending = None;
+        if let Some(i) = self.index {
+            self.index = Some(i.saturating_sub(1));
+        }
+    }
+
+    pub fn select_first(&mut self) {
+        self.pending = None;
+        if !self.is_empty() {
+            self.index = Some(0);
+        }
+    }
+
+    pub fn select_last(&mut self) {
+        self.pending = None;
+        if !self.is_empty() {
+            self.index = Some(self.len() - 1);
+        }
+    }
+
+    // Selects the entry at `path`, returning false if it isn't listed. While
+    // loading, it will be selected once it turns up
+    pub fn select_path(&mut self, path: &path::Path) -> bool {
+        match self.inner.iter().position(|e| e.path.as_path() == path) {
+            Some(i) => {
+                self.index = Some(i);
+                self.pending = None;
+                true
+            }
+            None => {
+                if self.is_loading() {
+                    self.pending = Some(path.to_path_buf());
+                }
+                false
+            }
+        }
+    }
+
+    // Toggles the flag on the selected entry
+    pub fn toggle_flag(&mut self) {
+        if let Some(entry) = self.index.and_then(|i| self.inner.get_mut(i)) {
+            entry.flagged = !entry.flagged;
+        }
+    }
+
+    // Paths of all flagged entries, or the selected entry if none are flagged
+    pub fn flagged_or_selected(&self) -> Vec<path::PathBuf> {
+        let flagged: Vec<path::PathBuf> = self
+            .iter()
+            .filter(|e| e.flagged)
+            .map(|e| e.path.clone())
+            .collect();
+
+        if flagged.is_empty() {
+            self.selected()
+                .map(|e| e.path.clone())
+                .into_iter()
+                .collect()
+        } else {
+            flagged
+        }
+    }
+
+    pub fn clear_flags(&mut self) {
+        self.iter_mut().for_each(|e| e.flagged = false);
+    }
+
     pub fn modified(&self) -> bool {
         let metadata = fs::symlink_metadata(&self.path);
         match metadata {
             Ok(m) => match m.modified() {
-                Ok(s) => s > self.metadata.modified,
+                Ok(s) => s > self.modified,
                 _ => false,
             },
             _ => false,
@@ -85,25 +363,13 @@ impl Directory {
     }
 }
 
-fn read_dir_list(path: &path::Path, config: &Config) -> io::Result<Vec<Entry>> {
-    let results = fs::read_dir(path)?
-        .filter(|res| {
-            if config.show_hidden {
-                true
-            } else {
-                match res {
-                    Err(_) => true,
-                    Ok(entry) => {
-                        let file_name = entry.file_name();
-                        let lossy_string = file_name.as_os_str().to_string_lossy();
-                        !lossy_string.starts_with('.')
-                    }
-                }
-            }
-        })
-        .filter_map(|res| Entry::from(&res.ok()?, config.show_icons).ok())
-        .collect();
-    Ok(results)
+// Reads an entry of a listing, skipping hidden ones unless they're shown
+pub fn read_entry(res: io::Result<fs::DirEntry>, config: &Config) -> Option<Entry> {
+    let entry = res.ok()?;
+    if !config.show_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+        return None;
+    }
+    Entry::from(&entry, config).ok()
 }
 
 impl Display for Directory {
@@ -1,8 +1,9 @@
-use super::metadata::{self, FileType, Metadata};
+use super::{icon, metadata::Metadata};
+use crate::config::Config;
 use std::fmt;
 use std::fs;
-use std::io::{self, BufRead, BufReader};
-use std::path::{self, Path};
+use std::io;
+use std::path;
 
 #[derive(Debug, Clone)]
 pub struct Entry {
@@ -14,7 +15,7 @@ pub struct Entry {
 }
 
 impl Entry {
-    pub fn from(dir_entry: &fs::DirEntry, show_icons: bool) -> io::Result<Self> {
+    pub fn from(dir_entry: &fs::DirEntry, config: &Config) -> io::Result<Self> {
         let name = dir_entry
             .file_name()
             .as_os_str()
@@ -23,20 +24,24 @@ impl Entry {
 
         let path = dir_entry.path();
         let metadata = Metadata::from(&path)?;
+        Ok(Self::new(name, path, metadata, config))
+    }
+
+    pub fn new(name: String, path: path::PathBuf, metadata: Metadata, config: &Config) -> Self {
         let flagged = false;
-        let label = if show_icons {
-            icon_label(&name, &path, &metadata)?
+        let label = if config.show_icons {
+            icon_label(&name, &metadata, config)
         } else {
             name.clone()
         };
 
-        Ok(Self {
+        Self {
             name,
             label,
             path,
             flagged,
             metadata,
-        })
+        }
     }
 
     pub fn get_extension(&self) -> &str {
@@ -47,30 +52,6 @@ impl Entry {
             ""
         }
     }
-
-    pub fn preview(&self, lines: usize) -> Result<String, Box<dyn std::error::Error>> {
-        match self.metadata.file_type {
-            metadata::FileType::File => {
-                let raw = self.read_n_lines(lines)?;
-                Ok(String::from_utf8(raw)?)
-            }
-            metadata::FileType::Directory(size) => Ok(format!("dir size: {}", size)),
-        }
-    }
-
-    fn read_n_lines(&self, lines: usize) -> io::Result<Vec<u8>> {
-        let file = fs::File::open(&self.path)?;
-        let line_reader = BufReader::new(file).lines();
-
-        let mut buf: Vec<u8> = Vec::new();
-        for (i, line) in line_reader.enumerate() {
-            if i > lines {
-                break;
-            }
-            buf.append(&mut (line.unwrap() + "\n").as_bytes().to_vec());
-        }
-        Ok(buf)
-    }
 }
 
 impl fmt::Display for Entry {
@@ -79,12 +60,6 @@ impl fmt::Display for Entry {
     }
 }
 
-fn icon_label(name: &str, path: &Path, md: &Metadata) -> io::Result<String> {
-    use super::icon::*;
-
-    let icon = match md.file_type {
-        FileType::Directory(_) => dir_node_exact_matches(name),
-        FileType::File => file_node_exact_matches(name, path),
-    };
-    Ok(format!("{} {}", icon, name))
+fn icon_label(name: &str, md: &Metadata, config: &Config) -> String {
+    format!("{} {}", icon::icon(name, md.is_dir(), &config.icons), name)
 }
@@ -1,11 +1,51 @@
-use std::path::Path;
+use crate::config::IconConfig;
+
+use serde::Deserialize;
+
+use std::{ffi::OsStr, path::Path};
 
 pub const DEFAULT_DIR: &str = "";
 pub const DEFAULT_FILE: &str = "";
 
+// Built in glyphs, for terminals without a nerd font
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IconSet {
+    #[default]
+    Nerd,
+    Emoji,
+    Ascii,
+}
+
+// Resolves the icon for an entry, preferring the user's overrides to the
+// built in set
+pub fn icon<'c>(name: &str, is_dir: bool, config: &'c IconConfig) -> &'c str {
+    let overridden = if is_dir {
+        config.directories.get(name)
+    } else {
+        let name = name.to_lowercase();
+        config
+            .names
+            .get(&name)
+            .or_else(|| config.extensions.get(extension(&name)))
+    };
+    if let Some(icon) = overridden {
+        return icon;
+    }
+
+    match (config.set, is_dir) {
+        (IconSet::Nerd, true) => dir_node_exact_matches(name),
+        (IconSet::Nerd, false) => file_node_exact_matches(name),
+        (IconSet::Emoji, true) => "📁",
+        (IconSet::Emoji, false) => file_node_emoji(extension(&name.to_lowercase())),
+        (IconSet::Ascii, true) => "d",
+        (IconSet::Ascii, false) => "-",
+    }
+}
+
 // For full font set, consider installing a third party font.
 // Recommended: https://github.com/ryanoasis/nerd-fonts
-pub fn dir_node_exact_matches(name: &str) -> &str {
+pub fn dir_node_exact_matches(name: &str) -> &'static str {
     match name {
         // English
         ".git" => "",
@@ -19,6 +59,15 @@ pub fn dir_node_exact_matches(name: &str) -> &str {
         "Public" => "",
         "Templates" => "",
         "Videos" => "",
+        // Hidden and development directories
+        ".cache" => "",
+        ".config" => "",
+        ".github" => "",
+        ".ssh" => "",
+        ".Trash" => "",
+        ".vscode" => "",
+        "node_modules" => "",
+        "Trash" => "",
         // Spanish
         "Escritorio" => "",
         "Documentos" => "",
@@ -67,8 +116,11 @@ pub fn dir_node_exact_matches(name: &str) -> &str {
     }
 }
 
-pub fn file_node_exact_matches<'s>(name: &str, path: &'s Path) -> &'s str {
-    match name {
+// Names are matched ignoring case, before falling back to the extension
+pub fn file_node_exact_matches(name: &str) -> &'static str {
+    let name = name.to_lowercase();
+    match name.as_str() {
+        ".babelrc" => "",
         ".bash_aliases" => "",
         ".bash_history" => "",
         ".bash_logout" => "",
@@ -76,107 +128,170 @@ pub fn file_node_exact_matches<'s>(name: &str, path: &'s Path) -> &'s str {
         ".bashprofile" => "",
         ".bashrc" => "",
         ".dmrc" => "",
-        ".DS_Store" => "",
+        ".dockerignore" => "",
+        ".ds_store" => "",
+        ".editorconfig" => "",
+        ".env" => "",
+        ".envrc" => "",
+        ".eslintrc" => "",
         ".fasd" => "",
         ".fehbg" => "",
         ".gitattributes" => "",
         ".gitconfig" => "",
         ".gitignore" => "",
+        ".gitkeep" => "",
         ".gitlab-ci.yml" => "",
+        ".gitmodules" => "",
         ".gvimrc" => "",
+        ".htaccess" => "",
+        ".htpasswd" => "",
         ".inputrc" => "",
         ".jack-settings" => "",
+        ".mailmap" => "",
         ".mime.types" => "",
         ".ncmpcpp" => "",
+        ".npmrc" => "",
         ".nvidia-settings-rc" => "",
+        ".nvmrc" => "",
         ".pam_environment" => "",
+        ".prettierrc" => "",
         ".profile" => "",
         ".recently-used" => "",
         ".selected_editor" => "",
+        ".tmux.conf" => "",
         ".vim" => "",
         ".viminfo" => "",
         ".vimrc" => "",
-        ".Xauthority" => "",
-        ".Xdefaults" => "",
+        ".xauthority" => "",
+        ".xdefaults" => "",
         ".xinitrc" => "",
         ".xinputrc" => "",
-        ".Xresources" => "",
+        ".xprofile" => "",
+        ".xresources" => "",
+        ".xsession" => "",
+        ".yarnrc" => "",
+        ".zlogin" => "",
+        ".zprofile" => "",
+        ".zshenv" => "",
         ".zshrc" => "",
         "_gvimrc" => "",
         "_vimrc" => "",
         "a.out" => "",
         "authorized_keys" => "",
         "bspwmrc" => "",
+        "cargo.lock" => "",
+        "cargo.toml" => "",
+        "changelog" => "",
+        "changelog.md" => "",
+        "changes.md" => "",
         "cmakelists.txt" => "",
+        "compose.yaml" => "",
+        "compose.yml" => "",
         "config" => "",
         "config.ac" => "",
         "config.m4" => "",
         "config.mk" => "",
         "config.ru" => "",
         "configure" => "",
+        "containerfile" => "",
+        "copying" => "",
+        "copying.lesser" => "",
+        "default.nix" => "",
+        "docker-compose.yaml" => "",
         "docker-compose.yml" => "",
         "dockerfile" => "",
-        "Dockerfile" => "",
         "dropbox" => "",
-        "exact-match-case-sensitive-1.txt" => "X1",
-        "exact-match-case-sensitive-2" => "X2",
         "favicon.ico" => "",
+        "flake.lock" => "",
+        "flake.nix" => "",
         "gemfile" => "",
+        "gemfile.lock" => "",
+        "gnumakefile" => "",
+        "go.mod" => "",
+        "go.sum" => "",
         "gruntfile.coffee" => "",
         "gruntfile.js" => "",
         "gruntfile.ls" => "",
         "gulpfile.coffee" => "",
         "gulpfile.js" => "",
         "gulpfile.ls" => "",
+        "history.md" => "",
+        "id_ecdsa" => "",
+        "id_ed25519" => "",
+        "id_rsa" => "",
         "ini" => "",
+        "init.vim" => "",
+        "jsconfig.json" => "",
+        "justfile" => "",
         "known_hosts" => "",
         "ledger" => "",
+        "licence" => "",
+        "licence.md" => "",
+        "licence.txt" => "",
         "license" => "",
-        "LICENSE" => "",
-        "LICENSE.md" => "",
-        "LICENSE.txt" => "",
-        "Makefile" => "",
+        "license.md" => "",
+        "license.txt" => "",
         "makefile" => "",
-        "Makefile.ac" => "",
-        "Makefile.in" => "",
+        "makefile.ac" => "",
+        "makefile.in" => "",
         "mimeapps.list" => "",
         "mix.lock" => "",
-        "node_modules" => "",
         "package-lock.json" => "",
         "package.json" => "",
+        "pipfile" => "",
+        "pipfile.lock" => "",
+        "pkgbuild" => "",
         "playlists" => "",
+        "pnpm-lock.yaml" => "",
+        "poetry.lock" => "",
         "procfile" => "",
-        "Rakefile" => "",
+        "pyproject.toml" => "",
         "rakefile" => "",
         "react.jsx" => "",
-        "README" => "",
-        "README.markdown" => "",
-        "README.md" => "",
-        "README.rst" => "",
-        "README.txt" => "",
+        "readme" => "",
+        "readme.markdown" => "",
+        "readme.md" => "",
+        "readme.rst" => "",
+        "readme.txt" => "",
+        "requirements.txt" => "",
+        "rust-toolchain" => "",
+        "rust-toolchain.toml" => "",
+        "setup.cfg" => "",
+        "setup.py" => "",
+        "shell.nix" => "",
         "sxhkdrc" => "",
+        "tsconfig.json" => "",
+        "unlicense" => "",
         "user-dirs.dirs" => "",
         "webpack.config.js" => "",
-        _ => file_node_extensions(match path.extension() {
-            Some(s) => s.to_str().unwrap(),
-            None => "",
-        }),
+        "yarn.lock" => "",
+        _ => file_node_extensions(extension(&name)),
     }
 }
 
-pub fn file_node_extensions(ext: &str) -> &str {
+pub fn file_node_extensions(ext: &str) -> &'static str {
     match ext {
         "7z" => "",
         "a" => "",
+        "aac" => "",
+        "adoc" => "",
         "ai" => "",
+        "aiff" => "",
         "apk" => "",
+        "appimage" => "",
+        "asc" => "",
         "asm" => "",
         "asp" => "",
+        "ass" => "",
         "aup" => "",
         "avi" => "",
+        "avif" => "",
         "awk" => "",
+        "azw3" => "",
         "bash" => "",
         "bat" => "",
+        "bib" => "",
+        "bin" => "",
         "bmp" => "",
         "bz2" => "",
         "c" => "",
@@ -185,6 +300,9 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "cbr" => "",
         "cbz" => "",
         "cc" => "",
+        "cer" => "",
+        "cfg" => "",
+        "cjs" => "",
         "class" => "",
         "clj" => "",
         "cljc" => "",
@@ -195,19 +313,29 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "cp" => "",
         "cpio" => "",
         "cpp" => "",
+        "crt" => "",
         "cs" => "",
         "csh" => "",
+        "csproj" => "",
         "css" => "",
+        "csv" => "",
+        "cts" => "",
         "cue" => "",
         "cvs" => "",
         "cxx" => "",
         "d" => "",
         "dart" => "",
+        "dat" => "",
         "db" => "",
+        "db3" => "",
         "deb" => "",
+        "desktop" => "",
         "diff" => "",
+        "djvu" => "",
         "dll" => "",
+        "dmg" => "",
         "doc" => "",
+        "dockerfile" => "",
         "docx" => "",
         "dump" => "",
         "edn" => "",
@@ -216,6 +344,7 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "ejs" => "",
         "elf" => "",
         "elm" => "",
+        "env" => "",
         "epub" => "",
         "erl" => "",
         "ex" => "",
@@ -234,11 +363,17 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "gemspec" => "",
         "gif" => "",
         "go" => "",
+        "gpg" => "",
+        "gql" => "",
+        "gradle" => "",
+        "graphql" => "",
+        "groovy" => "",
         "gz" => "",
         "gzip" => "",
         "h" => "",
         "haml" => "",
         "hbs" => "",
+        "heic" => "",
         "hh" => "",
         "hpp" => "",
         "hrl" => "",
@@ -251,6 +386,7 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "ico" => "",
         "img" => "",
         "ini" => "",
+        "ipynb" => "",
         "iso" => "",
         "jar" => "",
         "java" => "",
@@ -259,52 +395,84 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "jpg" => "",
         "js" => "",
         "json" => "",
+        "json5" => "",
+        "jsonc" => "",
         "jsx" => "",
+        "jxl" => "",
         "key" => "",
         "ksh" => "",
+        "kt" => "",
+        "kts" => "",
         "leex" => "",
         "less" => "",
         "lha" => "",
         "lhs" => "",
+        "lock" => "",
         "log" => "",
         "lua" => "",
+        "lz" => "",
+        "lz4" => "",
         "lzh" => "",
         "lzma" => "",
+        "m" => "",
+        "m3u" => "",
         "m4a" => "",
         "m4v" => "",
+        "mak" => "",
         "markdown" => "",
         "md" => "",
         "mdx" => "",
+        "mid" => "",
+        "midi" => "",
         "mjs" => "",
+        "mk" => "",
         "mkv" => "",
         "ml" => "λ",
         "mli" => "λ",
+        "mm" => "",
+        "mobi" => "",
         "mov" => "",
         "mp3" => "",
         "mp4" => "",
         "mpeg" => "",
         "mpg" => "",
         "msi" => "",
+        "mts" => "",
         "mustache" => "",
         "nix" => "",
         "o" => "",
+        "odp" => "",
+        "ods" => "",
+        "odt" => "",
         "ogg" => "",
+        "ogv" => "",
+        "opus" => "",
+        "org" => "",
+        "otf" => "",
+        "patch" => "",
         "pdf" => "",
+        "pem" => "",
         "php" => "",
+        "pkg" => "",
         "pl" => "",
         "pm" => "",
         "png" => "",
         "pp" => "",
         "ppt" => "",
         "pptx" => "",
+        "properties" => "",
         "ps1" => "",
         "psb" => "",
         "psd" => "",
+        "psm1" => "",
         "pub" => "",
         "py" => "",
         "pyc" => "",
         "pyd" => "",
+        "pyi" => "",
         "pyo" => "",
+        "pyw" => "",
+        "qcow2" => "",
         "r" => "ﳒ",
         "rake" => "",
         "rar" => "",
@@ -317,6 +485,7 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "rproj" => "鉶",
         "rs" => "",
         "rss" => "",
+        "rst" => "",
         "rtf" => "",
         "s" => "",
         "sass" => "",
@@ -327,25 +496,46 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "sln" => "",
         "so" => "",
         "sql" => "",
+        "sqlite" => "",
+        "sqlite3" => "",
+        "srt" => "",
         "styl" => "",
         "suo" => "",
+        "svg" => "",
         "swift" => "",
         "t" => "",
         "tar" => "",
+        "tbz2" => "",
         "tex" => "ﭨ",
         "tgz" => "",
+        "tif" => "",
+        "tiff" => "",
         "toml" => "",
+        "torrent" => "",
         "ts" => "",
+        "tsv" => "",
         "tsx" => "",
+        "ttf" => "",
         "twig" => "",
+        "txt" => "",
+        "txz" => "",
+        "vcxproj" => "",
         "vim" => "",
         "vimrc" => "",
+        "vtt" => "",
         "vue" => "﵂",
+        "war" => "",
+        "wasm" => "",
         "wav" => "",
         "webm" => "",
         "webmanifest" => "",
         "webp" => "",
+        "wma" => "",
+        "wmv" => "",
+        "woff" => "",
+        "woff2" => "",
         "xbps" => "",
+        "xcf" => "",
         "xcplayground" => "",
         "xhtml" => "",
         "xls" => "",
@@ -357,6 +547,29 @@ pub fn file_node_extensions(ext: &str) -> &str {
         "yml" => "",
         "zip" => "",
         "zsh" => "",
+        "zst" => "",
         _ => DEFAULT_FILE,
     }
 }
+
+pub fn file_node_emoji(ext: &str) -> &'static str {
+    match ext {
+        "7z" | "bz2" | "deb" | "gz" | "rar" | "rpm" | "tar" | "tgz" | "xz" | "zip" | "zst" => "📦",
+        "avif" | "bmp" | "gif" | "heic" | "ico" | "jpeg" | "jpg" | "png" | "svg" | "tiff"
+        | "webp" => "🎨",
+        "aac" | "flac" | "m4a" | "mp3" | "ogg" | "opus" | "wav" => "🎵",
+        "avi" | "flv" | "m4v" | "mkv" | "mov" | "mp4" | "mpeg" | "webm" => "🎬",
+        "doc" | "docx" | "epub" | "odt" | "pdf" | "rtf" => "📕",
+        "markdown" | "md" | "rst" | "txt" => "📝",
+        "crt" | "gpg" | "key" | "pem" | "pub" => "🔑",
+        "lock" => "🔒",
+        _ => "📄",
+    }
+}
+
+fn extension(name: &str) -> &str {
+    Path::new(name)
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or("")
+}
@@ -0,0 +1,249 @@
+use super::operation::{self, Unmonitored};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// A single reversible change made to the filesystem
+#[derive(Debug, Clone)]
+pub enum Operation {
+    Copy { from: PathBuf, to: PathBuf },
+    Move { from: PathBuf, to: PathBuf },
+    Rename { from: PathBuf, to: PathBuf },
+    Trash { from: PathBuf, to: PathBuf },
+    Mkdir(PathBuf),
+    Create(PathBuf),
+}
+
+impl Operation {
+    fn undo(&self) -> io::Result<()> {
+        match self {
+            Operation::Copy { to, .. } => operation::remove_recursive(to),
+            Operation::Move { from, to }
+            | Operation::Rename { from, to }
+            | Operation::Trash { from, to } => {
+                if fs::symlink_metadata(from).is_ok() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", from.display()),
+                    ));
+                }
+                operation::move_path(to, from, &mut Unmonitored)
+            }
+            // Only succeeds if nothing has been put in it since
+            Operation::Mkdir(path) => fs::remove_dir(path),
+            Operation::Create(path) => fs::remove_file(path),
+        }
+    }
+
+    // Paths whose parent listings change when this operation is applied or undone
+    fn paths(&self) -> Vec<&Path> {
+        match self {
+            Operation::Copy { from, to }
+            | Operation::Move { from, to }
+            | Operation::Rename { from, to }
+            | Operation::Trash { from, to } => vec![from, to],
+            Operation::Mkdir(path) | Operation::Create(path) => vec![path],
+        }
+    }
+}
+
+// History of mutating actions, grouped by the command which performed them
+#[derive(Debug, Clone)]
+pub struct Journal(Vec<Vec<Operation>>);
+
+impl Journal {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn record(&mut self, operations: Vec<Operation>) {
+        if !operations.is_empty() {
+            self.0.push(operations)
+        }
+    }
+
+    // Reverts the most recent group of operations, returning the directories
+    // which were affected along with whether it all went through. Operations
+    // which fail to revert are kept so the undo can be retried, and the
+    // directories are returned either way, as some may have been reverted.
+    pub fn undo(&mut self) -> (Vec<PathBuf>, io::Result<()>) {
+        let mut operations = match self.0.pop() {
+            Some(operations) => operations,
+            None => return (Vec::new(), Ok(())),
+        };
+
+        let mut affected = Vec::new();
+        let mut result = Ok(());
+        while let Some(operation) = operations.pop() {
+            for path in operation.paths() {
+                if let Some(parent) = path.parent() {
+                    affected.push(parent.to_path_buf());
+                }
+            }
+
+            if let Err(e) = operation.undo() {
+                operations.push(operation);
+                self.0.push(operations);
+                result = Err(e);
+                break;
+            }
+        }
+
+        affected.sort();
+        affected.dedup();
+        (affected, result)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undoing_a_copy_removes_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("a"), dir.path().join("b"));
+        fs::write(&from, "a").unwrap();
+        fs::copy(&from, &to).unwrap();
+
+        let mut journal = Journal::new();
+        journal.record(vec![Operation::Copy {
+            from: from.clone(),
+            to: to.clone(),
+        }]);
+        let (affected, result) = journal.undo();
+
+        result.unwrap();
+        assert_eq!(affected, vec![dir.path().to_path_buf()]);
+        assert!(from.exists());
+        assert!(!to.exists());
+        assert!(journal.is_empty());
+    }
+
+    #[test]
+    fn undoing_moves_and_renames_restores_the_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        let (moved, renamed) = (dir.path().join("moved"), dir.path().join("renamed"));
+        fs::write(sub.join("moved"), "moved").unwrap();
+        fs::write(dir.path().join("renamed"), "renamed").unwrap();
+        fs::rename(sub.join("moved"), &moved).unwrap();
+        fs::rename(&renamed, dir.path().join("new name")).unwrap();
+
+        let mut journal = Journal::new();
+        journal.record(vec![
+            Operation::Move {
+                from: sub.join("moved"),
+                to: moved.clone(),
+            },
+            Operation::Rename {
+                from: renamed.clone(),
+                to: dir.path().join("new name"),
+            },
+        ]);
+        let (affected, result) = journal.undo();
+
+        result.unwrap();
+        assert_eq!(affected, vec![dir.path().to_path_buf(), sub.clone()]);
+        assert_eq!(fs::read_to_string(sub.join("moved")).unwrap(), "moved");
+        assert_eq!(fs::read_to_string(&renamed).unwrap(), "renamed");
+        assert!(!moved.exists());
+        assert!(!dir.path().join("new name").exists());
+    }
+
+    #[test]
+    fn undo_never_overwrites_what_took_the_original_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("a"), dir.path().join("b"));
+        fs::write(&to, "renamed").unwrap();
+        fs::write(&from, "new").unwrap();
+
+        let mut journal = Journal::new();
+        journal.record(vec![Operation::Rename {
+            from: from.clone(),
+            to: to.clone(),
+        }]);
+        let (_, result) = journal.undo();
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&from).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to).unwrap(), "renamed");
+        // Kept so it can be retried once the way is clear
+        assert!(!journal.is_empty());
+        fs::remove_file(&from).unwrap();
+        journal.undo().1.unwrap();
+        assert_eq!(fs::read_to_string(&from).unwrap(), "renamed");
+    }
+
+    #[test]
+    fn undoing_mkdir_and_create_removes_only_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let (new_dir, new_file) = (dir.path().join("dir"), dir.path().join("file"));
+        let other = dir.path().join("other");
+        fs::create_dir(&new_dir).unwrap();
+        fs::write(&new_file, "").unwrap();
+        fs::write(&other, "").unwrap();
+
+        let mut journal = Journal::new();
+        journal.record(vec![Operation::Mkdir(new_dir.clone())]);
+        journal.record(vec![Operation::Create(new_file.clone())]);
+        journal.undo().1.unwrap();
+        journal.undo().1.unwrap();
+
+        assert!(!new_dir.exists());
+        assert!(!new_file.exists());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn undoing_mkdir_keeps_what_was_put_inside() {
+        let dir = tempfile::tempdir().unwrap();
+        let new_dir = dir.path().join("dir");
+        fs::create_dir(&new_dir).unwrap();
+        fs::write(new_dir.join("kept"), "").unwrap();
+
+        let mut journal = Journal::new();
+        journal.record(vec![Operation::Mkdir(new_dir.clone())]);
+
+        assert!(journal.undo().1.is_err());
+        assert!(new_dir.join("kept").exists());
+        assert!(!journal.is_empty());
+    }
+
+    #[test]
+    fn partly_failed_undo_reports_what_it_touched() {
+        let dir = tempfile::tempdir().unwrap();
+        let (one, two) = (dir.path().join("one"), dir.path().join("two"));
+        fs::create_dir_all(one.join("dir")).unwrap();
+        fs::create_dir(&two).unwrap();
+        fs::write(one.join("dir/blocker"), "").unwrap();
+        fs::write(two.join("file"), "").unwrap();
+
+        // Undone last to first, so the file goes before the mkdir fails
+        let mut journal = Journal::new();
+        journal.record(vec![
+            Operation::Mkdir(one.join("dir")),
+            Operation::Create(two.join("file")),
+        ]);
+        let (affected, result) = journal.undo();
+
+        assert!(result.is_err());
+        assert_eq!(affected, vec![one.clone(), two.clone()]);
+        assert!(!two.join("file").exists());
+        assert!(one.join("dir").exists());
+
+        // Only the operation which failed is left to retry
+        fs::remove_file(one.join("dir/blocker")).unwrap();
+        let (affected, result) = journal.undo();
+        result.unwrap();
+        assert_eq!(affected, vec![one.clone()]);
+        assert!(!one.join("dir").exists());
+        assert!(journal.is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+use super::{archive::Archive, directory::read_entry, Directory, Entry};
+use crate::config::Config;
+
+use crossbeam_channel::{Receiver, Sender};
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+// Results are sent back once this many have been read, or this long has
+// passed, whichever comes first
+const BATCH_SIZE: usize = 512;
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub enum LoadEvent {
+    Entries(usize, PathBuf, Vec<Entry>),
+    // Numbers of entries in subdirectories of the listing
+    Counts(usize, PathBuf, Vec<(PathBuf, usize)>),
+    Finished(usize, PathBuf),
+    // An archive's index, read in full before anything inside can be listed
+    Archive(usize, PathBuf, io::Result<Archive>),
+}
+
+type LoadChannel = (Sender<LoadEvent>, Receiver<LoadEvent>);
+
+// Reads directories and archive indexes on tokio's blocking pool, streaming
+// entries back so slow or huge directories don't stall the render loop.
+// Subdirectories are counted in a separate pass, once the listing is shown
+#[derive(Debug, Clone)]
+pub struct Loader {
+    channel: LoadChannel,
+    // Cancellation flags of running loads, by id
+    loads: HashMap<usize, Arc<AtomicBool>>,
+    next_id: usize,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self {
+            channel: crossbeam_channel::unbounded(),
+            loads: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    // Starts reading `path`, returning an empty listing for its entries to
+    // be merged into. The directory is opened up front, so a missing or
+    // unreadable one fails straight away
+    pub fn spawn(&mut self, path: &Path, config: &Config) -> io::Result<Directory> {
+        let (id, modified) = self.restart(path, config)?;
+        Ok(Directory::loading(path.to_path_buf(), modified, id))
+    }
+
+    // Starts reading `path` again for a listing which is already shown,
+    // returning the id of the load and when the directory was modified
+    pub fn restart(&mut self, path: &Path, config: &Config) -> io::Result<(usize, SystemTime)> {
+        let read_dir = fs::read_dir(path)?;
+        let modified = fs::metadata(path)?.modified()?;
+
+        let (id, cancelled) = self.start();
+        let sender = self.channel.0.clone();
+        let path = path.to_path_buf();
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut batch = Batch::new();
+            for res in read_dir {
+                if cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                let entries = match read_entry(res, &config).and_then(|e| batch.push(e)) {
+                    Some(entries) => entries,
+                    None => continue,
+                };
+                if sender
+                    .send(LoadEvent::Entries(id, path.clone(), entries))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+
+            // The loader may have been dropped on exit
+            let _ = sender.send(LoadEvent::Entries(id, path.clone(), batch.items));
+            let _ = sender.send(LoadEvent::Finished(id, path));
+        });
+
+        Ok((id, modified))
+    }
+
+    // Starts reading the index of the archive at `path`, returning the id
+    // of the load and when the archive was modified. Compressed tarballs
+    // are decompressed in full to be indexed, so this can take a while
+    pub fn open_archive(&mut self, path: &Path) -> io::Result<(usize, SystemTime)> {
+        let modified = fs::metadata(path)?.modified()?;
+
+        let (id, _) = self.start();
+        let sender = self.channel.0.clone();
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let archive = Archive::open(&path);
+            let _ = sender.send(LoadEvent::Archive(id, path, archive));
+        });
+
+        Ok((id, modified))
+    }
+
+    // Starts counting the entries in a listing's subdirectories, replacing
+    // any count already running
+    pub fn count(&mut self, dir: &mut Directory) {
+        if let Some(id) = dir.counting.take() {
+            self.cancel(id);
+        }
+        let targets = dir.subdirectories();
+        if targets.is_empty() {
+            return;
+        }
+
+        let (id, cancelled) = self.start();
+        dir.counting = Some(id);
+        let sender = self.channel.0.clone();
+        let path = dir.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut batch = Batch::new();
+            for target in targets {
+                if cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                // Unreadable directories are left uncounted
+                let count = match fs::read_dir(&target) {
+                    Ok(read_dir) => read_dir.count(),
+                    Err(_) => continue,
+                };
+                let counts = match batch.push((target, count)) {
+                    Some(counts) => counts,
+                    None => continue,
+                };
+                if sender
+                    .send(LoadEvent::Counts(id, path.clone(), counts))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+
+            let _ = sender.send(LoadEvent::Counts(id, path.clone(), batch.items));
+            let _ = sender.send(LoadEvent::Finished(id, path));
+        });
+    }
+
+    // Stops a load, leaving whatever it has read so far
+    pub fn cancel(&mut self, id: usize) {
+        if let Some(cancelled) = self.loads.remove(&id) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Takes the events sent since the last poll, forgetting loads which
+    // have finished
+    pub fn poll(&mut self) -> Vec<LoadEvent> {
+        let events: Vec<LoadEvent> = self.channel.1.try_iter().collect();
+        for event in events.iter() {
+            if let LoadEvent::Finished(id, _) | LoadEvent::Archive(id, _, _) = event {
+                self.loads.remove(id);
+            }
+        }
+        events
+    }
+
+    fn start(&mut self) -> (usize, Arc<AtomicBool>) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.loads.insert(id, cancelled.clone());
+        (id, cancelled)
+    }
+}
+
+// Results gathered on the blocking pool, waiting to be sent back
+struct Batch<T> {
+    items: Vec<T>,
+    last_sent: Instant,
+}
+
+impl<T> Batch<T> {
+    fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            last_sent: Instant::now(),
+        }
+    }
+
+    // Adds an item, returning the batch once it is due to be sent
+    fn push(&mut self, item: T) -> Option<Vec<T>> {
+        self.items.push(item);
+        if self.items.len() < BATCH_SIZE && self.last_sent.elapsed() < BATCH_INTERVAL {
+            return None;
+        }
+        self.last_sent = Instant::now();
+        Some(std::mem::take(&mut self.items))
+    }
+}
@@ -0,0 +1,152 @@
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+// How search and filter patterns are interpreted
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Substring,
+    Glob,
+    Regex,
+}
+
+// Matches entry names against a pattern typed by the user
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pub pattern: String,
+    kind: MatcherKind,
+}
+
+#[derive(Debug, Clone)]
+enum MatcherKind {
+    Substring { needle: String, ignore_case: bool },
+    Regex(Regex),
+}
+
+impl Matcher {
+    // With `smart_case`, patterns without capitals ignore case
+    pub fn new(pattern: &str, mode: MatchMode, smart_case: bool) -> Result<Self, regex::Error> {
+        let ignore_case = smart_case && !pattern.chars().any(char::is_uppercase);
+        let regex = |source: &str| {
+            RegexBuilder::new(source)
+                .case_insensitive(ignore_case)
+                .build()
+        };
+
+        let kind = match mode {
+            MatchMode::Substring => MatcherKind::Substring {
+                needle: match ignore_case {
+                    true => pattern.to_lowercase(),
+                    false => pattern.to_string(),
+                },
+                ignore_case,
+            },
+            MatchMode::Glob => MatcherKind::Regex(regex(&glob_to_regex(pattern))?),
+            MatchMode::Regex => MatcherKind::Regex(regex(pattern)?),
+        };
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            kind,
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        match &self.kind {
+            MatcherKind::Substring {
+                needle,
+                ignore_case: true,
+            } => name.to_lowercase().contains(needle.as_str()),
+            MatcherKind::Substring { needle, .. } => name.contains(needle.as_str()),
+            MatcherKind::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+// Translates a shell glob (`*`, `?`, `[...]`) into a regex matching whole names
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                if let Some('!') = chars.peek() {
+                    chars.next();
+                    regex.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, mode: MatchMode, smart_case: bool, name: &str) -> bool {
+        Matcher::new(pattern, mode, smart_case)
+            .unwrap()
+            .is_match(name)
+    }
+
+    #[test]
+    fn translates_globs() {
+        assert_eq!(glob_to_regex("*.rs"), r"^.*\.rs$");
+        assert_eq!(glob_to_regex("a?c"), "^a.c$");
+        assert_eq!(glob_to_regex("[!ab]x"), "^[^ab]x$");
+        assert_eq!(glob_to_regex("[a[\\]"), r"^[a\[\\]$");
+        assert_eq!(glob_to_regex("(1)+"), r"^\(1\)\+$");
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        assert!(matches("*.rs", MatchMode::Glob, false, "main.rs"));
+        assert!(!matches("*.rs", MatchMode::Glob, false, "main.rs.bak"));
+        assert!(matches("file?.[ct]xt", MatchMode::Glob, false, "file1.txt"));
+        assert!(!matches(
+            "file?.[!ct]xt",
+            MatchMode::Glob,
+            false,
+            "file1.txt"
+        ));
+    }
+
+    #[test]
+    fn smart_case_ignores_case_without_capitals() {
+        for mode in [MatchMode::Substring, MatchMode::Glob, MatchMode::Regex] {
+            let pattern = match mode {
+                MatchMode::Glob => "*read*",
+                _ => "read",
+            };
+            assert!(matches(pattern, mode, true, "README.md"), "{:?}", mode);
+            assert!(!matches(pattern, mode, false, "README.md"), "{:?}", mode);
+
+            let pattern = pattern.replace("read", "Read");
+            assert!(!matches(&pattern, mode, true, "README.md"), "{:?}", mode);
+            assert!(matches(&pattern, mode, true, "ReadMe.md"), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn invalid_regexes_are_errors() {
+        assert!(Matcher::new("(", MatchMode::Regex, true).is_err());
+        assert!(matches("(", MatchMode::Substring, true, "a (1)"));
+    }
+}
@@ -3,7 +3,8 @@ use std::{fs, io, path, time};
 #[derive(Debug, Clone)]
 pub enum FileType {
     File,
-    Directory(usize),
+    // Number of entries, once they've been counted in the background
+    Directory(Option<usize>),
 }
 
 #[derive(Debug, Clone)]
@@ -21,11 +22,11 @@ pub struct UnixData {
 
 #[derive(Debug, Clone)]
 pub struct Metadata {
-    len: u64,
+    pub len: u64,
     pub modified: time::SystemTime,
-    permissions: fs::Permissions,
+    pub permissions: fs::Permissions,
     pub file_type: FileType,
-    link_type: LinkType,
+    pub link_type: LinkType,
     #[cfg(unix)]
     pub unix_data: UnixData,
 }
@@ -42,8 +43,7 @@ impl Metadata {
         let modified = metadata.modified()?;
         let permissions = metadata.permissions();
         let file_type = if metadata.is_dir() {
-            let dir_size = fs::read_dir(path).map(|s| s.count())?;
-            FileType::Directory(dir_size)
+            FileType::Directory(None)
         } else {
             FileType::File
         };
@@ -79,4 +79,39 @@ impl Metadata {
             unix_data,
         })
     }
+
+    // Metadata for a member of the archive this describes, which otherwise
+    // only records sizes
+    pub fn member(&self, len: u64, file_type: FileType) -> Self {
+        let mut metadata = self.clone();
+        metadata.len = len;
+        metadata.link_type = LinkType::Normal;
+        #[cfg(unix)]
+        if let FileType::Directory(_) = file_type {
+            metadata.unix_data.mode = metadata.unix_data.mode & !0o170000 | 0o040000;
+        }
+        metadata.file_type = file_type;
+        metadata
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self.file_type, FileType::Directory(_))
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        matches!(self.link_type, LinkType::Symlink(_))
+    }
+
+    // Links are executable when their target is, rather than always
+    #[cfg(unix)]
+    pub fn is_executable(&self) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+
+        !self.is_dir() && self.permissions.mode() & 0o111 != 0
+    }
+
+    #[cfg(not(unix))]
+    pub fn is_executable(&self) -> bool {
+        false
+    }
 }
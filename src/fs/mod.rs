@@ -1,7 +1,29 @@
+pub mod archive;
 mod cache;
 mod directory;
 mod entry;
 mod icon;
+mod journal;
+mod loader;
+mod matcher;
 mod metadata;
+mod navigation;
+pub mod operation;
+mod register;
+mod sort;
+pub mod util;
+mod watcher;
 
-pub use self::{cache::Cache, directory::Directory, entry::Entry, metadata::Metadata};
+pub use self::{
+    cache::Cache,
+    directory::Directory,
+    entry::Entry,
+    icon::IconSet,
+    journal::{Journal, Operation},
+    matcher::{MatchMode, Matcher},
+    metadata::{FileType, LinkType},
+    navigation::{load_preview, navigate},
+    register::{Register, RegisterMode},
+    sort::{SortMode, SORT_MODES},
+    watcher::Watcher,
+};
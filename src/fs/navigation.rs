@@ -0,0 +1,100 @@
+use super::{archive::ArchiveKind, metadata::FileType, Cache};
+use crate::{config::Config, event::Movement};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+// Applies a movement to the directory at `path`, returning the path of the
+// directory the cursor ends up in
+pub fn navigate(
+    cache: &mut Cache,
+    path: &Path,
+    movement: &Movement,
+    config: &Config,
+) -> io::Result<PathBuf> {
+    match movement {
+        Movement::Up => {
+            if let Some(dir) = cache.get_mut(path) {
+                dir.select_prev();
+            }
+            Ok(path.to_path_buf())
+        }
+        Movement::Down => {
+            if let Some(dir) = cache.get_mut(path) {
+                dir.select_next();
+            }
+            Ok(path.to_path_buf())
+        }
+        Movement::Top => {
+            if let Some(dir) = cache.get_mut(path) {
+                dir.select_first();
+            }
+            Ok(path.to_path_buf())
+        }
+        Movement::Bottom => {
+            if let Some(dir) = cache.get_mut(path) {
+                dir.select_last();
+            }
+            Ok(path.to_path_buf())
+        }
+        Movement::Left => enter_parent(cache, path, config),
+        Movement::Right => enter_selected(cache, path, config),
+        // File inspection isn't supported yet
+        Movement::In | Movement::Out => Ok(path.to_path_buf()),
+    }
+}
+
+fn enter_parent(cache: &mut Cache, path: &Path, config: &Config) -> io::Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) => parent,
+        None => return Ok(path.to_path_buf()),
+    };
+
+    // Restore the cursor to the directory we came from
+    let dir = cache.load(parent, config)?;
+    dir.select_path(path);
+
+    Ok(parent.to_path_buf())
+}
+
+// Archives are entered as if they were directories
+fn enter_selected(cache: &mut Cache, path: &Path, config: &Config) -> io::Result<PathBuf> {
+    if let Some(archive) = selected_archive(cache, path) {
+        cache.open_archive(&archive)?;
+        cache.load(&archive, config)?;
+        return Ok(archive);
+    }
+
+    match selected_directory(cache, path) {
+        Some(target) => {
+            cache.load(&target, config)?;
+            Ok(target)
+        }
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+// Loads the selected directory into the cache so it can be previewed
+pub fn load_preview(cache: &mut Cache, path: &Path, config: &Config) -> io::Result<()> {
+    if let Some(target) = selected_directory(cache, path) {
+        cache.load(&target, config)?;
+    }
+    Ok(())
+}
+
+fn selected_archive(cache: &Cache, path: &Path) -> Option<PathBuf> {
+    let entry = cache.get(path)?.selected()?;
+    match entry.metadata.file_type {
+        FileType::File => ArchiveKind::from_name(&entry.name).map(|_| entry.path.clone()),
+        FileType::Directory(_) => None,
+    }
+}
+
+fn selected_directory(cache: &Cache, path: &Path) -> Option<PathBuf> {
+    let entry = cache.get(path)?.selected()?;
+    match entry.metadata.file_type {
+        FileType::Directory(_) => Some(entry.path.clone()),
+        FileType::File => None,
+    }
+}
@@ -0,0 +1,403 @@
+use super::{archive, journal::Operation};
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+// Receives progress from long running operations
+pub trait Monitor {
+    // Called as bytes are written and files completed, returning false to
+    // cancel the operation
+    fn advance(&mut self, bytes: u64, files: usize) -> bool;
+
+    // Called when `path` is done with in one step, like a rename, rather
+    // than file by file
+    fn complete(&mut self, _path: &Path) -> bool {
+        self.advance(0, 1)
+    }
+}
+
+// A monitor for operations which don't report progress
+pub struct Unmonitored;
+
+impl Monitor for Unmonitored {
+    fn advance(&mut self, _: u64, _: usize) -> bool {
+        true
+    }
+}
+
+// Copies or moves each source into `dest`, recording every completed
+// operation in `done` so a partial paste can still be undone
+pub fn paste(
+    sources: &[PathBuf],
+    dest: &Path,
+    cut: bool,
+    done: &mut Vec<Operation>,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    for source in sources {
+        if dest.starts_with(source) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot paste {} into itself", source.display()),
+            ));
+        }
+
+        let name = match source.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        // Moving something to where it already is leaves it alone
+        if cut && source.parent() == Some(dest) {
+            continue;
+        }
+        let target = unique_path(&dest.join(name));
+
+        let (from, to) = (source.clone(), target);
+        if cut {
+            move_path(&from, &to, monitor)?;
+            done.push(Operation::Move { from, to });
+        } else if let Some((archive, inner)) = archive::locate(&from) {
+            archive::extract(&archive, &inner, &to, monitor).map_err(|e| discard(&to, e))?;
+            done.push(Operation::Copy { from, to });
+        } else {
+            copy_recursive(&from, &to, monitor).map_err(|e| discard(&to, e))?;
+            done.push(Operation::Copy { from, to });
+        }
+    }
+
+    Ok(())
+}
+
+// Extracts an archive into `dest`, creating it if it doesn't exist
+pub fn extract(
+    archive: &Path,
+    dest: &Path,
+    done: &mut Vec<Operation>,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    if fs::symlink_metadata(dest).is_ok() {
+        for (member, to) in archive::extract_all(archive, dest, monitor)? {
+            let from = archive.join(member);
+            done.push(Operation::Copy { from, to });
+        }
+        return Ok(());
+    }
+
+    fs::create_dir(dest)?;
+    archive::extract_all(archive, dest, monitor).map_err(|e| discard(dest, e))?;
+    done.push(Operation::Copy {
+        from: archive.to_path_buf(),
+        to: dest.to_path_buf(),
+    });
+    Ok(())
+}
+
+// Compresses `sources` into a new archive at `to`
+pub fn compress(
+    sources: &[PathBuf],
+    to: &Path,
+    done: &mut Vec<Operation>,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    if let Some(source) = sources.iter().find(|source| to.starts_with(source)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot compress {} into itself", source.display()),
+        ));
+    }
+
+    archive::create(sources, to, monitor).map_err(|e| match e.kind() {
+        // Never remove something which was already there
+        io::ErrorKind::AlreadyExists => e,
+        _ => discard(to, e),
+    })?;
+    done.push(Operation::Create(to.to_path_buf()));
+    Ok(())
+}
+
+// Moves each path into suha's trash directory
+pub fn trash(
+    paths: &[PathBuf],
+    done: &mut Vec<Operation>,
+    monitor: &mut dyn Monitor,
+) -> io::Result<()> {
+    let trash_dir = trash_dir()?;
+    fs::create_dir_all(&trash_dir)?;
+
+    for path in paths {
+        let name = match path.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let target = unique_path(&trash_dir.join(name));
+
+        move_path(path, &target, monitor)?;
+        done.push(Operation::Trash {
+            from: path.clone(),
+            to: target,
+        });
+    }
+
+    Ok(())
+}
+
+fn trash_dir() -> io::Result<PathBuf> {
+    match home::home_dir() {
+        Some(home) => Ok(home.join(".local/share/suha/trash")),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no home directory for the trash",
+        )),
+    }
+}
+
+// Copies a file, symlink or directory tree to `to`
+pub fn copy_recursive(from: &Path, to: &Path, monitor: &mut dyn Monitor) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        copy_symlink(from, to)?;
+        advance(monitor, 0, 1)
+    } else if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()), monitor)?;
+        }
+        fs::set_permissions(to, metadata.permissions())
+    } else {
+        copy_file(from, to, monitor)?;
+        fs::set_permissions(to, metadata.permissions())?;
+        advance(monitor, 0, 1)
+    }
+}
+
+fn copy_file(from: &Path, to: &Path, monitor: &mut dyn Monitor) -> io::Result<()> {
+    write_file(&mut fs::File::open(from)?, to, monitor)
+}
+
+// Writes a file in chunks so progress can be reported and cancelled
+pub fn write_file(reader: &mut dyn Read, to: &Path, monitor: &mut dyn Monitor) -> io::Result<()> {
+    let mut writer = fs::File::create(to)?;
+    let mut buf = vec![0; COPY_BUFFER_SIZE];
+
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..n])?;
+        advance(monitor, n as u64, 0)?;
+    }
+}
+
+// Renames `from` to `to`, falling back to copying and removing the source
+// when they're on different filesystems
+pub fn move_path(from: &Path, to: &Path, monitor: &mut dyn Monitor) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) if monitor.complete(from) => return Ok(()),
+        Ok(()) => return Err(cancelled()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e),
+    }
+
+    copy_recursive(from, to, monitor).map_err(|e| discard(to, e))?;
+    remove_recursive(from)
+}
+
+pub fn remove_recursive(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// Total size in bytes and number of files under `path`, which may be inside
+// an archive
+pub fn measure(path: &Path) -> io::Result<(u64, usize)> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => match archive::locate(path) {
+            Some((archive, inner)) => return archive::measure(&archive, &inner),
+            None => return Err(e),
+        },
+    };
+    if !metadata.is_dir() {
+        return Ok((metadata.len(), 1));
+    }
+
+    let mut totals = (0, 0);
+    for entry in fs::read_dir(path)? {
+        let (bytes, files) = measure(&entry?.path())?;
+        totals.0 += bytes;
+        totals.1 += files;
+    }
+    Ok(totals)
+}
+
+// Finds a free path by appending `_n` to the file stem, eg. `notes_1.txt`
+pub fn unique_path(path: &Path) -> PathBuf {
+    if fs::symlink_metadata(path).is_err() {
+        return path.to_path_buf();
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|i| path.with_file_name(format!("{}_{}{}", stem, i, extension)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .unwrap()
+}
+
+pub fn advance(monitor: &mut dyn Monitor, bytes: u64, files: usize) -> io::Result<()> {
+    if monitor.advance(bytes, files) {
+        Ok(())
+    } else {
+        Err(cancelled())
+    }
+}
+
+// Cancellation isn't reported as `Interrupted`, as readers and writers
+// retry on that, and would never stop
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+fn cancelled() -> io::Error {
+    io::Error::other(Cancelled)
+}
+
+// Whether an operation failed because it was cancelled
+pub fn is_cancelled(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|e| e.is::<Cancelled>())
+}
+
+// Removes a partially written copy, passing on the error which interrupted it
+fn discard(path: &Path, err: io::Error) -> io::Error {
+    let _ = remove_recursive(path);
+    err
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cut_into_same_directory_does_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "notes").unwrap();
+
+        let mut done = Vec::new();
+        paste(
+            std::slice::from_ref(&file),
+            dir.path(),
+            true,
+            &mut done,
+            &mut Unmonitored,
+        )
+        .unwrap();
+
+        assert!(done.is_empty());
+        assert!(file.exists());
+        assert!(!dir.path().join("notes_1.txt").exists());
+    }
+
+    #[test]
+    fn cut_moves_into_another_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        let dest = dir.path().join("dest");
+        fs::write(&file, "notes").unwrap();
+        fs::create_dir(&dest).unwrap();
+
+        let mut done = Vec::new();
+        paste(
+            std::slice::from_ref(&file),
+            &dest,
+            true,
+            &mut done,
+            &mut Unmonitored,
+        )
+        .unwrap();
+
+        assert_eq!(done.len(), 1);
+        assert!(!file.exists());
+        assert_eq!(fs::read_to_string(dest.join("notes.txt")).unwrap(), "notes");
+    }
+
+    #[test]
+    fn failed_rename_is_not_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+
+        let err = move_path(&missing, &dir.path().join("to"), &mut Unmonitored).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    // Records how progress was reported
+    #[derive(Default)]
+    struct Recorder {
+        advanced: usize,
+        completed: Vec<PathBuf>,
+    }
+
+    impl Monitor for Recorder {
+        fn advance(&mut self, _: u64, _: usize) -> bool {
+            self.advanced += 1;
+            true
+        }
+
+        fn complete(&mut self, path: &Path) -> bool {
+            self.completed.push(path.to_path_buf());
+            true
+        }
+    }
+
+    #[test]
+    fn renamed_directory_completes_in_one_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir_all(tree.join("a/b")).unwrap();
+        fs::write(tree.join("a/b/file"), "file").unwrap();
+
+        let mut recorder = Recorder::default();
+        move_path(&tree, &dir.path().join("moved"), &mut recorder).unwrap();
+
+        assert_eq!(recorder.completed, vec![tree]);
+        assert_eq!(recorder.advanced, 0);
+        assert!(dir.path().join("moved/a/b/file").exists());
+    }
+}
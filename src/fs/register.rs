@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterMode {
+    Copy,
+    Cut,
+}
+
+// Holds the paths captured by a copy or cut, waiting to be pasted
+#[derive(Debug, Clone)]
+pub struct Register {
+    pub mode: RegisterMode,
+    pub paths: Vec<PathBuf>,
+}
+
+impl Register {
+    pub fn new() -> Self {
+        Self {
+            mode: RegisterMode::Copy,
+            paths: Vec::new(),
+        }
+    }
+
+    pub fn set(&mut self, mode: RegisterMode, paths: Vec<PathBuf>) {
+        self.mode = mode;
+        self.paths = paths;
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
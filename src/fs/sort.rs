@@ -0,0 +1,128 @@
+use super::{metadata::FileType, Entry};
+use crate::config::SortConfig;
+
+use serde::Deserialize;
+
+use std::{cmp::Ordering, fmt::Display};
+
+// Keys that directory listings can be sorted by
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    // Names, with runs of digits compared by value
+    #[default]
+    Natural,
+    // Largest first
+    Size,
+    // Most recent first
+    Modified,
+    Extension,
+    // Directories, then symlinks, executables and other files
+    Type,
+}
+
+pub const SORT_MODES: [(&str, SortMode); 5] = [
+    ("natural", SortMode::Natural),
+    ("size", SortMode::Size),
+    ("modified", SortMode::Modified),
+    ("extension", SortMode::Extension),
+    ("type", SortMode::Type),
+];
+
+impl SortMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        SORT_MODES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, mode)| *mode)
+    }
+}
+
+impl Display for SortMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = SORT_MODES
+            .iter()
+            .find(|(_, mode)| mode == self)
+            .map_or("", |(name, _)| name);
+        write!(f, "{}", name)
+    }
+}
+
+// Sorts entries by the configured key, falling back to their names for ties.
+// Reversing doesn't move directories from the top when they're listed first
+pub fn sort(entries: &mut [Entry], config: &SortConfig) {
+    entries.sort_by(|a, b| compare(a, b, config));
+}
+
+// Merges two sorted listings into one
+pub fn merge(a: Vec<Entry>, b: Vec<Entry>, config: &SortConfig) -> Vec<Entry> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) if compare(x, y, config) == Ordering::Greater => b.next(),
+            (Some(_), _) => a.next(),
+            (None, _) => b.next(),
+        };
+        match next {
+            Some(entry) => merged.push(entry),
+            None => return merged,
+        }
+    }
+}
+
+fn compare(a: &Entry, b: &Entry, config: &SortConfig) -> Ordering {
+    let dirs_first = match config.dirs_first {
+        true => is_dir(b).cmp(&is_dir(a)),
+        false => Ordering::Equal,
+    };
+    let order = compare_by(a, b, config.mode).then_with(|| natural(a, b));
+    dirs_first.then(match config.reverse {
+        true => order.reverse(),
+        false => order,
+    })
+}
+
+fn compare_by(a: &Entry, b: &Entry, mode: SortMode) -> Ordering {
+    match mode {
+        SortMode::Natural => Ordering::Equal,
+        SortMode::Size => size(b).cmp(&size(a)),
+        SortMode::Modified => b.metadata.modified.cmp(&a.metadata.modified),
+        SortMode::Extension => a
+            .get_extension()
+            .to_lowercase()
+            .cmp(&b.get_extension().to_lowercase()),
+        SortMode::Type => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+fn natural(a: &Entry, b: &Entry) -> Ordering {
+    alphanumeric_sort::compare_str(&a.name, &b.name)
+}
+
+fn is_dir(entry: &Entry) -> bool {
+    entry.metadata.is_dir()
+}
+
+// Directories are sized by their number of entries, or nothing until
+// they've been counted
+fn size(entry: &Entry) -> u64 {
+    match entry.metadata.file_type {
+        FileType::Directory(count) => count.unwrap_or(0) as u64,
+        FileType::File => entry.metadata.len,
+    }
+}
+
+fn type_rank(entry: &Entry) -> u8 {
+    let metadata = &entry.metadata;
+    if metadata.is_dir() {
+        0
+    } else if metadata.is_symlink() {
+        1
+    } else if metadata.is_executable() {
+        2
+    } else {
+        3
+    }
+}
@@ -1 +1,122 @@
-// pub fn sort()
\ No newline at end of file
+use chrono::{DateTime, Local};
+
+use std::{fmt::Write, time::SystemTime};
+
+const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+// Formats a byte count with binary units, eg. `1.5 MiB`
+pub fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// Formats a unix mode like `ls -l`, eg. `drwxr-xr-x`
+pub fn permission_string(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o010000 => 'p',
+        0o140000 => 's',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        _ => '-',
+    };
+
+    let mut string = String::with_capacity(10);
+    string.push(kind);
+    // Owner, group and other, each with the special bit shown in place of x
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+// Formats how long ago a time was, eg. `5m ago`
+pub fn relative_time(time: SystemTime) -> String {
+    let seconds = match SystemTime::now().duration_since(time) {
+        Ok(elapsed) => elapsed.as_secs(),
+        Err(_) => return "future".to_string(),
+    };
+
+    let (amount, unit) = match seconds {
+        0..=59 => return "now".to_string(),
+        60..=3_599 => (seconds / 60, "m"),
+        3_600..=86_399 => (seconds / 3_600, "h"),
+        86_400..=2_591_999 => (seconds / 86_400, "d"),
+        2_592_000..=31_535_999 => (seconds / 2_592_000, "mo"),
+        _ => (seconds / 31_536_000, "y"),
+    };
+    format!("{}{} ago", amount, unit)
+}
+
+// Formats a time in the local timezone with a strftime style format, falling
+// back to RFC 3339 if the format is invalid
+pub fn format_time(time: SystemTime, format: &str) -> String {
+    let time = DateTime::<Local>::from(time);
+    let mut string = String::new();
+    match write!(string, "{}", time.format(format)) {
+        Ok(()) => string,
+        Err(_) => time.to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_permissions_like_ls() {
+        assert_eq!(permission_string(0o100644), "-rw-r--r--");
+        assert_eq!(permission_string(0o040755), "drwxr-xr-x");
+        assert_eq!(permission_string(0o120777), "lrwxrwxrwx");
+        assert_eq!(permission_string(0o010600), "prw-------");
+        assert_eq!(permission_string(0o000000), "----------");
+    }
+
+    #[test]
+    fn shows_special_bits_in_place_of_execute() {
+        assert_eq!(permission_string(0o104755), "-rwsr-xr-x");
+        assert_eq!(permission_string(0o102745), "-rwxr-Sr-x");
+        assert_eq!(permission_string(0o041777), "drwxrwxrwt");
+        assert_eq!(permission_string(0o041776), "drwxrwxrwT");
+    }
+
+    #[test]
+    fn sizes_use_binary_units() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(u64::MAX), "16384.0 PiB");
+    }
+
+    #[test]
+    fn relative_times_round_down() {
+        let ago = |secs| relative_time(SystemTime::now() - Duration::from_secs(secs));
+        assert_eq!(ago(30), "now");
+        assert_eq!(ago(150), "2m ago");
+        assert_eq!(ago(2 * 86_400), "2d ago");
+        assert_eq!(
+            relative_time(SystemTime::now() + Duration::from_secs(60)),
+            "future"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use super::Cache;
+
+use crossbeam_channel::Receiver;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use std::{
+    collections::{HashMap, HashSet},
+    iter,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+// How often directories without a native watch are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Changes are held until a directory has been quiet this long, so a burst
+// of events reloads it once. One which never goes quiet is still reloaded
+// after the longer delay
+const DEBOUNCE: Duration = Duration::from_millis(200);
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+type EventReceiver = Receiver<notify::Result<notify::Event>>;
+
+// Notices when cached directories change on disk. The OS reports changes
+// where it can (inotify on Linux), and any directory it can't watch is
+// checked by its modification time instead
+pub struct Watcher {
+    native: Option<RecommendedWatcher>,
+    events: EventReceiver,
+    // Directories the OS is watching for us
+    watched: HashSet<PathBuf>,
+    // Directories the OS refused to watch, eg. once inotify runs out of
+    // watches
+    polled: HashSet<PathBuf>,
+    last_poll: Instant,
+    // Directories which have changed, with when they first and last did
+    pending: HashMap<PathBuf, (Instant, Instant)>,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        let (sender, events) = crossbeam_channel::unbounded();
+        Self {
+            native: notify::recommended_watcher(sender).ok(),
+            events,
+            watched: HashSet::new(),
+            polled: HashSet::new(),
+            last_poll: Instant::now(),
+            pending: HashMap::new(),
+        }
+    }
+
+    // Paths of cached directories which have changed since the last call,
+    // once they've settled. Ones still loading are held until they finish
+    pub fn changed(&mut self, cache: &Cache) -> Vec<PathBuf> {
+        self.sync(cache);
+
+        // Events name the entries which changed, so their parents are the
+        // listings to refresh
+        let mut changed = HashSet::new();
+        while let Ok(event) = self.events.try_recv() {
+            let paths = match event {
+                Ok(event) => event.paths,
+                Err(e) => e.paths,
+            };
+            for path in paths.iter() {
+                for dir in iter::once(path.as_path()).chain(path.parent()) {
+                    if cache.get(dir).is_some() {
+                        changed.insert(dir.to_path_buf());
+                    }
+                }
+            }
+        }
+
+        if self.last_poll.elapsed() >= POLL_INTERVAL {
+            self.last_poll = Instant::now();
+            for (path, dir) in cache.as_ref() {
+                if !self.watched.contains(path) && dir.modified() {
+                    changed.insert(path.clone());
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for path in changed {
+            self.pending.entry(path).or_insert((now, now)).1 = now;
+        }
+
+        let mut settled = Vec::new();
+        self.pending.retain(|path, (first, last)| {
+            let dir = match cache.get(path) {
+                Some(dir) => dir,
+                None => return false,
+            };
+            let due = now - *last >= DEBOUNCE || now - *first >= MAX_DELAY;
+            if due && !dir.is_loading() {
+                settled.push(path.clone());
+                return false;
+            }
+            true
+        });
+        settled
+    }
+
+    // Watches directories as they're cached, and stops once they're dropped
+    fn sync(&mut self, cache: &Cache) {
+        let native = match self.native.as_mut() {
+            Some(native) => native,
+            None => return,
+        };
+
+        self.watched.retain(|path| {
+            let cached = cache.get(path).is_some();
+            if !cached {
+                let _ = native.unwatch(path);
+            }
+            cached
+        });
+        self.polled.retain(|path| cache.get(path).is_some());
+
+        for path in cache.as_ref().keys() {
+            if self.watched.contains(path) || self.polled.contains(path) {
+                continue;
+            }
+            match native.watch(path, RecursiveMode::NonRecursive) {
+                Ok(()) => self.watched.insert(path.clone()),
+                Err(_) => self.polled.insert(path.clone()),
+            };
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use super::{Progress, Task};
+use crate::fs::{
+    operation::{self, Monitor},
+    Operation,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    slice::Iter,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// Minimum time between progress messages sent by a running job
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum JobEvent {
+    Progress(usize, Progress),
+    Finished(usize, Vec<Operation>, io::Result<()>),
+}
+
+type JobChannel = (Sender<JobEvent>, Receiver<JobEvent>);
+
+#[derive(Debug)]
+pub struct Job {
+    pub id: usize,
+    pub task: Task,
+    pub progress: Progress,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// A job which has stopped running, successfully or otherwise
+#[derive(Debug)]
+pub struct FinishedJob {
+    pub task: Task,
+    pub operations: Vec<Operation>,
+    pub result: io::Result<()>,
+}
+
+impl FinishedJob {
+    // Cancelled jobs stop with an error, but haven't failed
+    pub fn was_cancelled(&self) -> bool {
+        matches!(&self.result, Err(e) if operation::is_cancelled(e))
+    }
+}
+
+// Runs file operations on tokio's blocking pool, so large transfers don't
+// stall the render loop
+#[derive(Debug)]
+pub struct JobManager {
+    channel: JobChannel,
+    jobs: Vec<Job>,
+    next_id: usize,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self {
+            channel: crossbeam_channel::unbounded(),
+            jobs: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn spawn(&mut self, task: Task) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut monitor = JobMonitor {
+            id,
+            progress: Progress::default(),
+            sender: self.channel.0.clone(),
+            cancelled: cancelled.clone(),
+            last_sent: Instant::now(),
+            measured: HashMap::new(),
+        };
+        let job_task = task.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut operations = Vec::new();
+            let result = monitor
+                .measure(&job_task)
+                .and_then(|_| job_task.run(&mut operations, &mut monitor));
+
+            // The manager may have been dropped on exit
+            let _ = monitor
+                .sender
+                .send(JobEvent::Finished(id, operations, result));
+        });
+
+        self.jobs.push(Job {
+            id,
+            task,
+            progress: Progress::default(),
+            cancelled,
+        });
+    }
+
+    // Cancels the most recently started job, returning false if none are running
+    pub fn cancel_last(&mut self) -> bool {
+        match self.jobs.iter().rev().find(|job| !job.is_cancelled()) {
+            Some(job) => {
+                job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Applies pending progress messages, returning any jobs which have finished
+    pub fn poll(&mut self) -> Vec<FinishedJob> {
+        let mut finished = Vec::new();
+        while let Ok(event) = self.channel.1.try_recv() {
+            match event {
+                JobEvent::Progress(id, progress) => {
+                    if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                        job.progress = progress;
+                    }
+                }
+                JobEvent::Finished(id, operations, result) => {
+                    if let Some(i) = self.jobs.iter().position(|job| job.id == id) {
+                        let job = self.jobs.remove(i);
+                        finished.push(FinishedJob {
+                            task: job.task,
+                            operations,
+                            result,
+                        });
+                    }
+                }
+            }
+        }
+        finished
+    }
+
+    pub fn iter(&self) -> Iter<'_, Job> {
+        self.jobs.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+struct JobMonitor {
+    id: usize,
+    progress: Progress,
+    sender: Sender<JobEvent>,
+    cancelled: Arc<AtomicBool>,
+    last_sent: Instant,
+    // Sizes of the task's paths, for those which complete all at once
+    measured: HashMap<PathBuf, (u64, usize)>,
+}
+
+impl JobMonitor {
+    // Totals up the work ahead so progress can be shown as a fraction
+    fn measure(&mut self, task: &Task) -> io::Result<()> {
+        for (path, bytes, files) in task.measure()? {
+            self.progress.total_bytes += bytes;
+            self.progress.total_files += files;
+            self.measured.insert(path, (bytes, files));
+        }
+        self.send();
+        Ok(())
+    }
+
+    fn send(&mut self) {
+        self.last_sent = Instant::now();
+        let _ = self.sender.send(JobEvent::Progress(self.id, self.progress));
+    }
+}
+
+impl Monitor for JobMonitor {
+    fn advance(&mut self, bytes: u64, files: usize) -> bool {
+        self.progress.bytes += bytes;
+        self.progress.files += files;
+        if self.last_sent.elapsed() >= PROGRESS_INTERVAL {
+            self.send();
+        }
+        !self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn complete(&mut self, path: &Path) -> bool {
+        let (bytes, files) = self.measured.get(path).copied().unwrap_or((0, 1));
+        self.advance(bytes, files)
+    }
+}
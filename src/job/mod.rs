@@ -0,0 +1,5 @@
+mod manager;
+mod task;
+
+pub use manager::*;
+pub use task::*;
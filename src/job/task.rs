@@ -0,0 +1,138 @@
+use crate::fs::{
+    archive,
+    operation::{self, Monitor},
+    util::human_size,
+    Operation,
+};
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
+
+// Work that runs in the background as a job
+#[derive(Debug, Clone)]
+pub enum Task {
+    Paste {
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+        cut: bool,
+    },
+    Trash(Vec<PathBuf>),
+    Extract {
+        archive: PathBuf,
+        dest: PathBuf,
+    },
+    Compress {
+        sources: Vec<PathBuf>,
+        archive: PathBuf,
+    },
+}
+
+impl Task {
+    // Size in bytes and number of files of each path the task will work
+    // through
+    pub fn measure(&self) -> io::Result<Vec<(PathBuf, u64, usize)>> {
+        let sources = match self {
+            Task::Paste { sources, .. } | Task::Compress { sources, .. } => sources,
+            Task::Trash(paths) => paths,
+            // Archives are measured by what they hold once extracted
+            Task::Extract { archive, .. } => {
+                let (bytes, files) = archive::measure(archive, Path::new(""))?;
+                return Ok(vec![(archive.clone(), bytes, files)]);
+            }
+        };
+
+        sources
+            .iter()
+            .map(|source| {
+                let (bytes, files) = operation::measure(source)?;
+                Ok((source.clone(), bytes, files))
+            })
+            .collect()
+    }
+
+    pub fn run(&self, done: &mut Vec<Operation>, monitor: &mut dyn Monitor) -> io::Result<()> {
+        match self {
+            Task::Paste { sources, dest, cut } => {
+                operation::paste(sources, dest, *cut, done, monitor)
+            }
+            Task::Trash(paths) => operation::trash(paths, done, monitor),
+            Task::Extract { archive, dest } => operation::extract(archive, dest, done, monitor),
+            Task::Compress { sources, archive } => {
+                operation::compress(sources, archive, done, monitor)
+            }
+        }
+    }
+
+    // Directories whose listings change once the task has run
+    pub fn affected(&self) -> Vec<PathBuf> {
+        let mut affected: Vec<PathBuf> = match self {
+            Task::Paste {
+                dest, cut: false, ..
+            } => vec![dest.clone()],
+            Task::Paste { sources, dest, .. } => parents(sources)
+                .chain(std::iter::once(dest.clone()))
+                .collect(),
+            Task::Trash(paths) => parents(paths).collect(),
+            Task::Extract { dest, .. } => parents(std::slice::from_ref(dest))
+                .chain(std::iter::once(dest.clone()))
+                .collect(),
+            Task::Compress { archive, .. } => parents(std::slice::from_ref(archive)).collect(),
+        };
+
+        affected.sort();
+        affected.dedup();
+        affected
+    }
+}
+
+fn parents(paths: &[PathBuf]) -> impl Iterator<Item = PathBuf> + '_ {
+    paths
+        .iter()
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+}
+
+impl Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Task::Paste { cut: false, .. } => "Copy",
+            Task::Paste { cut: true, .. } => "Move",
+            Task::Trash(_) => "Delete",
+            Task::Extract { .. } => "Extract",
+            Task::Compress { .. } => "Compress",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Progress {
+    pub bytes: u64,
+    pub total_bytes: u64,
+    pub files: usize,
+    pub total_files: usize,
+}
+
+impl Progress {
+    pub fn percent(&self) -> u64 {
+        match self.total_bytes {
+            0 => 0,
+            total => self.bytes.min(total) * 100 / total,
+        }
+    }
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} files, {} / {} ({}%)",
+            self.files,
+            self.total_files,
+            human_size(self.bytes),
+            human_size(self.total_bytes),
+            self.percent()
+        )
+    }
+}
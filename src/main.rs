@@ -3,8 +3,11 @@
 mod app;
 mod config;
 mod context;
+mod dispatch;
 mod event;
 mod fs;
+mod job;
+mod preview;
 mod ui;
 
 use app::App;
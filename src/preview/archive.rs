@@ -0,0 +1,155 @@
+use super::CACHE_SIZE;
+use crate::fs::{
+    archive::{self as archives, ArchiveKind, Member},
+    util::human_size,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// Wide enough for sizes like `1023.9 KiB`
+const SIZE_WIDTH: usize = 10;
+
+type ListingResult = Result<Listing, String>;
+type ListingChannel = (
+    Sender<(PathBuf, SystemTime, ListingResult)>,
+    Receiver<(PathBuf, SystemTime, ListingResult)>,
+);
+
+// An archive's members along with their totals, so drawing it only touches
+// the lines that are shown
+pub struct Listing {
+    members: Vec<Member>,
+    files: usize,
+    total: u64,
+}
+
+impl Listing {
+    fn new(members: Vec<Member>) -> Self {
+        let files = members.iter().filter(|m| !m.is_dir).count();
+        let total = members.iter().map(|m| m.size).sum();
+        Self {
+            members,
+            files,
+            total,
+        }
+    }
+
+    // Lists the members under a summary line, at most `height` lines in all
+    pub fn lines(&self, height: usize) -> Vec<Spans<'static>> {
+        let dim = Style::default().fg(Color::DarkGray);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+
+        let mut lines = vec![Spans::from(Span::styled(
+            format!(
+                "{} files, {} uncompressed",
+                self.files,
+                human_size(self.total)
+            ),
+            bold,
+        ))];
+
+        let shown = self.members.len().min(height.saturating_sub(1));
+        for member in self.members[..shown].iter() {
+            let path = member.path.to_string_lossy();
+            let line = match member.is_dir {
+                true => Spans::from(vec![
+                    Span::styled(format!("{:>w$}  ", "-", w = SIZE_WIDTH), dim),
+                    Span::styled(format!("{}/", path.trim_end_matches('/')), bold),
+                ]),
+                false => Spans::from(vec![
+                    Span::styled(
+                        format!("{:>w$}  ", human_size(member.size), w = SIZE_WIDTH),
+                        dim,
+                    ),
+                    Span::raw(path.into_owned()),
+                ]),
+            };
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+// Lists archives on tokio's blocking pool, as tarballs have to be
+// decompressed in full to be listed. Failures are kept as well as
+// listings, so a broken archive isn't read again until it's modified
+pub struct Listings {
+    channel: ListingChannel,
+    // Listings by path and modification time, with `None` while reading
+    listings: HashMap<PathBuf, (SystemTime, Option<ListingResult>)>,
+    // Listed paths, oldest first
+    order: VecDeque<PathBuf>,
+}
+
+impl Listings {
+    pub fn new() -> Self {
+        Self {
+            channel: crossbeam_channel::unbounded(),
+            listings: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    // The listing of the archive at `path`, starting to read it if it
+    // hasn't been since it was last modified
+    pub fn get(
+        &mut self,
+        path: &Path,
+        kind: ArchiveKind,
+        modified: SystemTime,
+    ) -> Option<&ListingResult> {
+        self.poll();
+
+        let current = matches!(self.listings.get(path), Some((m, _)) if *m == modified);
+        if !current {
+            self.spawn(path, kind, modified);
+        }
+        self.listings.get(path).and_then(|(_, res)| res.as_ref())
+    }
+
+    fn spawn(&mut self, path: &Path, kind: ArchiveKind, modified: SystemTime) {
+        let sender = self.channel.0.clone();
+        let path = path.to_path_buf();
+        if self
+            .listings
+            .insert(path.clone(), (modified, None))
+            .is_none()
+        {
+            self.order.push_back(path.clone());
+        }
+        while self.order.len() > CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.listings.remove(&oldest);
+            }
+        }
+
+        tokio::task::spawn_blocking(move || {
+            let res = archives::members(&path, kind)
+                .map(Listing::new)
+                .map_err(|e| e.to_string());
+            let _ = sender.send((path, modified, res));
+        });
+    }
+
+    // Stores finished listings, unless the archive has changed or been
+    // evicted since they were started
+    fn poll(&mut self) {
+        while let Ok((path, modified, res)) = self.channel.1.try_recv() {
+            if let Some((m, listing)) = self.listings.get_mut(&path) {
+                if *m == modified {
+                    *listing = Some(res);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+};
+
+const OFFSET_WIDTH: usize = 8;
+// Rows hold as many bytes as fit the pane, halving from the widest
+const ROW_BYTES: [usize; 5] = [16, 8, 4, 2, 1];
+// Bytes are split into groups of this many
+const GROUP_BYTES: usize = 8;
+// Reading this far into a file is enough to tell whether it's text
+const SNIFF_BYTES: usize = 8 * 1024;
+
+// Files are binary if a NUL turns up anywhere in their first few KiB, as
+// text almost never has one, or if those bytes hold much more than a
+// sprinkling of control characters and invalid UTF-8
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sniffed = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if sniffed.contains(&0) {
+        return true;
+    }
+
+    let mut suspicious = sniffed
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    let mut rest = sniffed;
+    while let Err(e) = std::str::from_utf8(rest) {
+        // A character cut off by the end of the sniffed bytes is fine
+        let len = match e.error_len() {
+            Some(len) => len,
+            None => break,
+        };
+        suspicious += len;
+        rest = &rest[e.valid_up_to() + len..];
+    }
+    suspicious * 10 > sniffed.len()
+}
+
+// Bytes shown per row of a dump `width` columns wide
+pub fn row_bytes(width: usize) -> usize {
+    ROW_BYTES
+        .iter()
+        .copied()
+        .find(|&n| row_width(n) <= width)
+        .unwrap_or(1)
+}
+
+// Dumps bytes as rows of offset, hex and ASCII columns, like `xxd`
+pub fn hexdump(bytes: &[u8], row_bytes: usize) -> Vec<Spans<'static>> {
+    let dim = Style::default().fg(Color::DarkGray);
+    bytes
+        .chunks(row_bytes)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let mut spans = vec![Span::styled(
+                format!("{:0width$x}  ", row * row_bytes, width = OFFSET_WIDTH),
+                dim,
+            )];
+
+            for (i, byte) in chunk.iter().enumerate() {
+                if i > 0 {
+                    spans.push(Span::raw(match i % GROUP_BYTES {
+                        0 => "  ",
+                        _ => " ",
+                    }));
+                }
+                spans.push(Span::styled(format!("{:02x}", byte), byte_style(*byte)));
+            }
+
+            // Pad short last rows so the ASCII column lines up
+            let padding = hex_width(row_bytes) - hex_width(chunk.len());
+            spans.push(Span::raw(" ".repeat(padding + 2)));
+            spans.extend(chunk.iter().map(|&byte| {
+                let c = match byte {
+                    0x20..=0x7e => byte as char,
+                    _ => '.',
+                };
+                Span::styled(c.to_string(), byte_style(byte))
+            }));
+            Spans::from(spans)
+        })
+        .collect()
+}
+
+fn row_width(row_bytes: usize) -> usize {
+    OFFSET_WIDTH + 2 + hex_width(row_bytes) + 2 + row_bytes
+}
+
+fn hex_width(bytes: usize) -> usize {
+    match bytes {
+        0 => 0,
+        n => n * 3 - 1 + (n - 1) / GROUP_BYTES,
+    }
+}
+
+// Colors bytes by kind, so text and padding stand out from the rest
+fn byte_style(byte: u8) -> Style {
+    let color = match byte {
+        0x00 => Color::DarkGray,
+        b'\t' | b'\n' | b'\r' | b' ' => Color::Green,
+        0x21..=0x7e => Color::Cyan,
+        0x01..=0x1f | 0x7f => Color::Magenta,
+        _ => Color::Yellow,
+    };
+    Style::default().fg(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(line: &Spans) -> String {
+        line.0.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn sniffs_binary_files() {
+        assert!(!is_binary(b"fn main() {\n\tprintln!(\"\x1b[1mhi\");\n}\n"));
+        assert!(!is_binary("naïve café".as_bytes()));
+        assert!(is_binary(b"text with a \0 in the middle"));
+        assert!(is_binary(&[0x7f, b'E', b'L', b'F', 2, 1, 1]));
+        assert!(is_binary(&[0xff; 64]));
+        // Only the start of a file is looked at
+        let mut late_nul = vec![b'a'; SNIFF_BYTES];
+        late_nul.push(0);
+        assert!(!is_binary(&late_nul));
+    }
+
+    #[test]
+    fn character_cut_off_at_the_end_is_text() {
+        let bytes = "é".repeat(10).into_bytes();
+        assert!(!is_binary(&bytes[..bytes.len() - 1]));
+    }
+
+    #[test]
+    fn rows_fit_the_width() {
+        assert_eq!(row_width(16), 76);
+        assert_eq!(row_bytes(80), 16);
+        assert_eq!(row_bytes(75), 8);
+        assert_eq!(row_bytes(0), 1);
+    }
+
+    #[test]
+    fn dumps_offsets_hex_and_ascii() {
+        let bytes: Vec<u8> = (0x41..0x41 + 18).collect();
+        let lines = hexdump(&bytes, 16);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            text(&lines[0]),
+            "00000000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50  ABCDEFGHIJKLMNOP"
+        );
+        // The short last row is padded so its ASCII column lines up
+        assert_eq!(
+            text(&lines[1]),
+            format!("00000010  51 52{}  QR", " ".repeat(43))
+        );
+        assert_eq!(text(&lines[1]).len(), row_width(16) - 14);
+    }
+
+    #[test]
+    fn unprintable_bytes_are_dots() {
+        let lines = hexdump(&[0, b'\n', b'a', 0xff], 4);
+        assert_eq!(text(&lines[0]), "00000000  00 0a 61 ff  ..a.");
+    }
+}
@@ -0,0 +1,112 @@
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Style as SyntectStyle, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+    thread,
+};
+
+// Used when the configured theme doesn't exist
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+struct Assets {
+    syntaxes: SyntaxSet,
+    themes: ThemeSet,
+}
+
+// Syntax definitions take a moment to load, so they're loaded on another
+// thread, with files shown plain until they're ready
+pub struct Highlighter {
+    assets: Arc<OnceLock<Assets>>,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        let assets = Arc::new(OnceLock::new());
+        let loading = assets.clone();
+        thread::spawn(move || {
+            let _ = loading.set(Assets {
+                syntaxes: SyntaxSet::load_defaults_newlines(),
+                themes: ThemeSet::load_defaults(),
+            });
+        });
+
+        Self { assets }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.assets.get().is_some()
+    }
+
+    // Highlights the first lines of the file `name`, returning nothing when
+    // its syntax isn't known
+    pub fn highlight(
+        &self,
+        name: &str,
+        lines: &[String],
+        theme: &str,
+    ) -> Option<Vec<Spans<'static>>> {
+        let assets = self.assets.get()?;
+        let syntax = find_syntax(&assets.syntaxes, name, lines.first()?)?;
+        if syntax.name == "Plain Text" {
+            return None;
+        }
+        let themes = &assets.themes.themes;
+        let theme = themes.get(theme).or_else(|| themes.get(DEFAULT_THEME))?;
+
+        // Syntaxes loaded with newlines expect each line to end in one
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        lines
+            .iter()
+            .map(|line| {
+                let line = format!("{}\n", line);
+                let ranges = highlighter.highlight_line(&line, &assets.syntaxes).ok()?;
+                let spans: Vec<Span> = ranges
+                    .into_iter()
+                    .map(|(style, text)| {
+                        Span::styled(text.trim_end_matches('\n').to_string(), convert(style))
+                    })
+                    .collect();
+                Some(Spans::from(spans))
+            })
+            .collect()
+    }
+}
+
+// Picks a syntax by extension, then by whole name for files like
+// "Makefile", then by the first line for shebangs and modelines
+fn find_syntax<'a>(
+    syntaxes: &'a SyntaxSet,
+    name: &str,
+    first_line: &str,
+) -> Option<&'a SyntaxReference> {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| syntaxes.find_syntax_by_extension(&extension.to_string_lossy()))
+        .or_else(|| syntaxes.find_syntax_by_extension(name))
+        .or_else(|| syntaxes.find_syntax_by_first_line(first_line))
+}
+
+// Only the foreground is kept, so previews sit on the terminal's background
+fn convert(style: SyntectStyle) -> Style {
+    let color = style.foreground;
+    let mut converted = Style::default().fg(Color::Rgb(color.r, color.g, color.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        converted = converted.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        converted = converted.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        converted = converted.add_modifier(Modifier::UNDERLINED);
+    }
+    converted
+}
@@ -0,0 +1,175 @@
+mod archive;
+mod hex;
+mod highlight;
+
+use archive::Listings;
+use highlight::Highlighter;
+
+use crate::{
+    config::PreviewConfig,
+    fs::{
+        archive::{self as archives, ArchiveKind},
+        Entry, FileType,
+    },
+};
+
+use tui::text::{Spans, Text};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+// Number of previews kept for files we might come back to
+const CACHE_SIZE: usize = 64;
+// Files are only read this far, so huge ones preview as quickly as small ones
+const MAX_BYTES: u64 = 64 * 1024;
+const TAB: &str = "    ";
+
+// Renders files for the preview pane, as highlighted text, a hex dump for
+// binaries or a listing for archives, caching them by path until they're modified
+pub struct Previewer {
+    highlighter: Highlighter,
+    listings: Listings,
+    cache: HashMap<PathBuf, Preview>,
+    // Cached paths, oldest first
+    order: VecDeque<PathBuf>,
+}
+
+struct Preview {
+    modified: SystemTime,
+    // Size of the pane rendered for, and whether the whole file fit
+    width: usize,
+    height: usize,
+    complete: bool,
+    text: Text<'static>,
+}
+
+impl Previewer {
+    pub fn new() -> Self {
+        Self {
+            highlighter: Highlighter::new(),
+            listings: Listings::new(),
+            cache: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    // Previews as much of an entry as fits in a pane `width` by `height`
+    pub fn preview(
+        &mut self,
+        entry: &Entry,
+        width: usize,
+        height: usize,
+        config: &PreviewConfig,
+    ) -> Text<'static> {
+        match entry.metadata.file_type {
+            FileType::Directory(Some(count)) => return Text::raw(format!("dir size: {}", count)),
+            FileType::Directory(None) => return Text::raw("dir size: counting…"),
+            FileType::File => {}
+        }
+
+        let modified = entry.metadata.modified;
+        if let Some(kind) = ArchiveKind::from_name(&entry.name) {
+            return match self.listings.get(&entry.path, kind, modified) {
+                Some(Ok(listing)) => Text::from(listing.lines(height)),
+                Some(Err(e)) => Text::raw(e.clone()),
+                None => Text::raw("reading archive…"),
+            };
+        }
+
+        if let Some(preview) = self.cache.get(&entry.path) {
+            let fits = preview.complete || preview.height >= height;
+            if preview.modified == modified && preview.width == width && fits {
+                return preview.text.clone();
+            }
+        }
+
+        let bytes = match read(entry) {
+            Ok(bytes) => bytes,
+            Err(e) => return Text::raw(e.to_string()),
+        };
+        if hex::is_binary(&bytes) {
+            let row_bytes = hex::row_bytes(width);
+            let shown = bytes.len().min(row_bytes * height);
+            let text = Text::from(hex::hexdump(&bytes[..shown], row_bytes));
+            let preview = Preview {
+                modified,
+                width,
+                height,
+                complete: shown == bytes.len() && (bytes.len() as u64) < MAX_BYTES,
+                text: text.clone(),
+            };
+            self.insert(entry.path.clone(), preview);
+            return text;
+        }
+
+        let (lines, complete) = text_lines(&bytes, height);
+        let highlighted = match config.highlight {
+            true => self
+                .highlighter
+                .highlight(&entry.name, &lines, &config.syntax_theme),
+            false => None,
+        };
+        let text = match highlighted {
+            Some(spans) => Text::from(spans),
+            None => Text::from(lines.into_iter().map(Spans::from).collect::<Vec<_>>()),
+        };
+
+        // Plain previews are only kept once they can't be highlighted
+        if !config.highlight || self.highlighter.is_ready() {
+            self.insert(
+                entry.path.clone(),
+                Preview {
+                    modified,
+                    width,
+                    height,
+                    complete,
+                    text: text.clone(),
+                },
+            );
+        }
+        text
+    }
+
+    fn insert(&mut self, path: PathBuf, preview: Preview) {
+        if self.cache.insert(path.clone(), preview).is_none() {
+            self.order.push_back(path);
+        }
+        while self.order.len() > CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+    }
+}
+
+// Files inside archives are read out of them
+fn read(entry: &Entry) -> io::Result<Vec<u8>> {
+    if let Some((archive, inner)) = archives::locate(&entry.path) {
+        return archives::read(&archive, &inner, MAX_BYTES);
+    }
+
+    let mut bytes = Vec::new();
+    fs::File::open(&entry.path)?
+        .take(MAX_BYTES)
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Splits text into at most `height` lines, along with whether that was the
+// whole file. Invalid UTF-8 is replaced rather than failing the preview
+fn text_lines(bytes: &[u8], height: usize) -> (Vec<String>, bool) {
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines: Vec<String> = text
+        .lines()
+        .take(height + 1)
+        .map(|line| line.replace('\t', TAB))
+        .collect();
+    let complete = lines.len() <= height && (bytes.len() as u64) < MAX_BYTES;
+    lines.truncate(height);
+    (lines, complete)
+}
@@ -0,0 +1,194 @@
+use crate::{
+    config::ColumnConfig,
+    fs::{
+        util::{format_time, human_size, relative_time},
+        Entry, FileType,
+    },
+};
+
+use serde::Deserialize;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// Names are kept at least this wide, dropping columns which don't fit
+const MIN_NAME_WIDTH: usize = 12;
+
+// Details which can be shown beside entries in the current directory
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Size,
+    Modified,
+    Permissions,
+    User,
+    Group,
+}
+
+impl Column {
+    pub fn cell(&self, entry: &Entry, config: &ColumnConfig, owners: &Owners) -> String {
+        let metadata = &entry.metadata;
+        match self {
+            Column::Size => match metadata.file_type {
+                FileType::Directory(Some(count)) => count.to_string(),
+                FileType::Directory(None) => String::new(),
+                FileType::File => human_size(metadata.len),
+            },
+            Column::Modified if config.relative_time => relative_time(metadata.modified),
+            Column::Modified => format_time(metadata.modified, &config.time_format),
+            Column::Permissions => permissions(entry),
+            Column::User => owners.user(entry),
+            Column::Group => owners.group(entry),
+        }
+    }
+
+    // Numbers line up on the right
+    fn right_aligned(&self) -> bool {
+        matches!(self, Column::Size)
+    }
+}
+
+// Lays out each entry's label followed by its columns, so they fill `width`.
+// Only the entries on screen are passed in, as formatting cells isn't free.
+// Returns nothing when no columns are configured
+pub fn column_lines(
+    entries: &[Entry],
+    config: &ColumnConfig,
+    owners: &Owners,
+    width: usize,
+) -> Option<Vec<String>> {
+    if config.show.is_empty() {
+        return None;
+    }
+
+    let cells: Vec<Vec<String>> = entries
+        .iter()
+        .map(|entry| {
+            config
+                .show
+                .iter()
+                .map(|column| column.cell(entry, config, owners))
+                .collect()
+        })
+        .collect();
+
+    // Keep the leading columns which fit beside the names
+    let mut widths = Vec::new();
+    let mut used = 0;
+    for i in 0..config.show.len() {
+        let column_width = cells.iter().map(|row| row[i].width()).max().unwrap_or(0);
+        if used + column_width + 1 + MIN_NAME_WIDTH > width {
+            break;
+        }
+        used += column_width + 1;
+        widths.push(column_width);
+    }
+    if widths.is_empty() {
+        return None;
+    }
+
+    let name_width = width - used;
+    let lines = entries
+        .iter()
+        .zip(cells)
+        .map(|(entry, row)| {
+            let mut line = pad(&truncate(&entry.label, name_width), name_width, false);
+            for (i, column_width) in widths.iter().enumerate() {
+                line.push(' ');
+                line += &pad(&row[i], *column_width, config.show[i].right_aligned());
+            }
+            line
+        })
+        .collect();
+    Some(lines)
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+
+    // Leave room for the ellipsis
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width + 1 > width {
+            break;
+        }
+        used += char_width;
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+fn pad(text: &str, width: usize, right_aligned: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(text.width()));
+    match right_aligned {
+        true => padding + text,
+        false => text.to_string() + &padding,
+    }
+}
+
+#[cfg(unix)]
+fn permissions(entry: &Entry) -> String {
+    crate::fs::util::permission_string(entry.metadata.unix_data.mode)
+}
+
+#[cfg(not(unix))]
+fn permissions(entry: &Entry) -> String {
+    match entry.metadata.permissions.readonly() {
+        true => "r-".to_string(),
+        false => "rw".to_string(),
+    }
+}
+
+// Looks up owner and group names, once each. Other platforms have no
+// unix owners, so their columns are left blank
+#[cfg(unix)]
+pub struct Owners(users::UsersCache);
+
+#[cfg(not(unix))]
+pub struct Owners;
+
+#[cfg(unix)]
+impl Owners {
+    pub fn new() -> Self {
+        Self(users::UsersCache::new())
+    }
+
+    // Owners are shown by name, or by id if they have none
+    fn user(&self, entry: &Entry) -> String {
+        use users::Users;
+
+        let uid = entry.metadata.unix_data.uid;
+        self.0
+            .get_user_by_uid(uid)
+            .map(|user| user.name().to_string_lossy().to_string())
+            .unwrap_or_else(|| uid.to_string())
+    }
+
+    fn group(&self, entry: &Entry) -> String {
+        use users::Groups;
+
+        let gid = entry.metadata.unix_data.gid;
+        self.0
+            .get_group_by_gid(gid)
+            .map(|group| group.name().to_string_lossy().to_string())
+            .unwrap_or_else(|| gid.to_string())
+    }
+}
+
+#[cfg(not(unix))]
+impl Owners {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn user(&self, _entry: &Entry) -> String {
+        String::new()
+    }
+
+    fn group(&self, _entry: &Entry) -> String {
+        String::new()
+    }
+}
@@ -0,0 +1,225 @@
+use crate::fs::Entry;
+
+use tui::style::{Color, Modifier, Style};
+
+use std::{cmp::Reverse, collections::HashMap, env};
+
+const ANSI_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+// Entry colors from the LS_COLORS variable set up by dircolors, eg.
+// "di=01;34:ln=01;36:*.tar=01;31". Links are styled as what they point to
+// with "ln=target"
+#[derive(Debug, Clone, Default)]
+pub struct LsColors {
+    // Styles for file types, keyed by their two letter codes
+    types: HashMap<String, Style>,
+    // Styles for lowercase name suffixes, longest first so the most
+    // specific one wins
+    suffixes: Vec<(String, Style)>,
+    // Set by "ln=target", styling links like whatever they point to
+    link_target: bool,
+}
+
+impl LsColors {
+    pub fn from_env() -> Self {
+        env::var("LS_COLORS")
+            .map(|spec| Self::parse(&spec))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(spec: &str) -> Self {
+        let mut ls_colors = Self::default();
+        for (key, codes) in spec.split(':').filter_map(|item| item.split_once('=')) {
+            if key == "ln" && codes == "target" {
+                ls_colors.link_target = true;
+                continue;
+            }
+            let style = parse_sgr(codes);
+            match key.strip_prefix('*') {
+                Some(suffix) => ls_colors.suffixes.push((suffix.to_lowercase(), style)),
+                None => {
+                    ls_colors.types.insert(key.to_string(), style);
+                }
+            }
+        }
+
+        ls_colors
+            .suffixes
+            .sort_by_key(|(suffix, _)| Reverse(suffix.len()));
+        ls_colors
+    }
+
+    // Like ls, a file's type takes precedence over its name
+    pub fn style(&self, entry: &Entry) -> Option<Style> {
+        let metadata = &entry.metadata;
+        let kind = if metadata.is_symlink() && !self.link_target {
+            "ln"
+        } else if metadata.is_dir() {
+            "di"
+        } else if metadata.is_executable() {
+            "ex"
+        } else {
+            "fi"
+        };
+
+        let type_style = self.types.get(kind).copied();
+        match kind {
+            "fi" => self.suffix_style(&entry.name).or(type_style),
+            "ex" => type_style.or_else(|| self.suffix_style(&entry.name)),
+            _ => type_style,
+        }
+    }
+
+    fn suffix_style(&self, name: &str) -> Option<Style> {
+        let name = name.to_lowercase();
+        self.suffixes
+            .iter()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, style)| *style)
+    }
+}
+
+// Converts SGR parameters, eg. "01;38;5;208", into a style
+fn parse_sgr(codes: &str) -> Style {
+    let mut style = Style::default();
+    let mut codes = codes.split(';').filter_map(|code| code.parse::<u8>().ok());
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(ANSI_COLORS[(code - 30) as usize]),
+            40..=47 => style.bg(ANSI_COLORS[(code - 40) as usize]),
+            90..=97 => style.fg(ANSI_COLORS[(code - 90 + 8) as usize]),
+            100..=107 => style.bg(ANSI_COLORS[(code - 100 + 8) as usize]),
+            38 => extended_color(&mut codes).map_or(style, |color| style.fg(color)),
+            48 => extended_color(&mut codes).map_or(style, |color| style.bg(color)),
+            39 => style.fg(Color::Reset),
+            49 => style.bg(Color::Reset),
+            _ => style,
+        };
+    }
+    style
+}
+
+// Reads the rest of a 256 color ("5;n") or true color ("2;r;g;b") parameter
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::Indexed(codes.next()?)),
+        2 => Some(Color::Rgb(codes.next()?, codes.next()?, codes.next()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only used by the symlink test, which needs unix
+    #[cfg(unix)]
+    fn entry(path: &std::path::Path) -> Entry {
+        let dir_entry = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(Result::unwrap)
+            .find(|e| e.path() == path)
+            .unwrap();
+        Entry::from(&dir_entry, &crate::config::Config::default()).unwrap()
+    }
+
+    #[test]
+    fn parses_attributes_and_colors() {
+        let style = parse_sgr("01;04;31;44");
+        assert_eq!(
+            style,
+            Style::default()
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+                .fg(Color::Red)
+                .bg(Color::Blue)
+        );
+        assert_eq!(
+            parse_sgr("91;103"),
+            Style::default().fg(Color::LightRed).bg(Color::LightYellow)
+        );
+    }
+
+    #[test]
+    fn parses_extended_colors() {
+        assert_eq!(
+            parse_sgr("38;5;208"),
+            Style::default().fg(Color::Indexed(208))
+        );
+        assert_eq!(
+            parse_sgr("48;2;1;2;3"),
+            Style::default().bg(Color::Rgb(1, 2, 3))
+        );
+        // Truncated parameters are dropped
+        assert_eq!(parse_sgr("38;2;1"), Style::default());
+    }
+
+    #[test]
+    fn reset_clears_earlier_codes() {
+        assert_eq!(parse_sgr("01;31;0;32"), Style::default().fg(Color::Green));
+        assert_eq!(
+            parse_sgr("junk;;1"),
+            Style::default().add_modifier(Modifier::BOLD)
+        );
+    }
+
+    #[test]
+    fn longest_suffix_wins_ignoring_case() {
+        let ls_colors = LsColors::parse("*.gz=31:*.tar.gz=32:fi=33");
+        assert_eq!(
+            ls_colors.suffix_style("A.TAR.GZ"),
+            Some(Style::default().fg(Color::Green))
+        );
+        assert_eq!(
+            ls_colors.suffix_style("a.gz"),
+            Some(Style::default().fg(Color::Red))
+        );
+        assert_eq!(ls_colors.suffix_style("a.txt"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn links_take_their_targets_style() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let link = dir.path().join("link");
+        std::fs::create_dir(&target).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let directory = Style::default().fg(Color::Blue);
+        let ls_colors = LsColors::parse("di=34:ln=36");
+        assert_eq!(
+            ls_colors.style(&entry(&link)),
+            Some(Style::default().fg(Color::Cyan))
+        );
+
+        let ls_colors = LsColors::parse("di=34:ln=target");
+        assert_eq!(ls_colors.style(&entry(&link)), Some(directory));
+        assert_eq!(ls_colors.style(&entry(&target)), Some(directory));
+    }
+}
@@ -1,3 +1,10 @@
+mod column;
+mod ls_colors;
 mod painter;
+mod status;
+mod theme;
 
+pub use column::Column;
+pub use ls_colors::LsColors;
 pub use painter::Painter;
+pub use theme::Theme;
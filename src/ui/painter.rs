@@ -1,20 +1,29 @@
 use crate::{
-    event::{Command, Worker},
-    fs::Cache,
+    config::Config,
+    event::{Command, Prompt},
+    fs::{Cache, Directory, Entry},
+    job::JobManager,
+    preview::Previewer,
+};
+
+use super::{
+    column::{column_lines, Owners},
+    status::{status_line, FreeSpace},
+    LsColors, Theme,
 };
 
 use crossterm::{cursor, execute, terminal};
-use tokio::sync::Mutex;
 use tui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
 };
+use unicode_width::UnicodeWidthStr;
 
 use std::{
+    collections::HashMap,
     io::{stdout, Stdout},
-    path::Path,
-    sync::Arc,
+    path::{Path, PathBuf},
 };
 
 macro_rules! constraints {
@@ -25,12 +34,19 @@ pub type Terminal = tui::Terminal<CrosstermBackend<Stdout>>;
 
 pub struct Painter {
     terminal: Terminal,
-    worker: Arc<Mutex<Worker>>,
     command: Command,
+    // Scroll offset of each visited directory, so the current
+    // selection stays in view between frames
+    offsets: HashMap<PathBuf, usize>,
+    ls_colors: LsColors,
+    // Owner and group names, looked up once each
+    owners: Owners,
+    previewer: Previewer,
+    free_space: FreeSpace,
 }
 
 impl Painter {
-    pub fn new(worker: Arc<Mutex<Worker>>) -> crossterm::Result<Self> {
+    pub fn new() -> crossterm::Result<Self> {
         let mut stdout = stdout();
         execute!(
             stdout,
@@ -46,24 +62,70 @@ impl Painter {
 
         Ok(Self {
             terminal,
-            worker,
             command: Command::None,
+            offsets: HashMap::new(),
+            ls_colors: LsColors::from_env(),
+            owners: Owners::new(),
+            previewer: Previewer::new(),
+            free_space: FreeSpace::new(),
         })
     }
 
-    pub async fn update(&mut self) {
-        if let Ok(command) = self.worker.lock().await.receive_command().await {
-            self.command = command
-        };
+    pub fn update(&mut self, command: Command) {
+        self.command = command;
     }
 
-    pub async fn render(&mut self, cache: &Cache, path: &Path) -> crossterm::Result<()> {
-        let command = self.command.to_string();
-        self.as_mut().draw(|frame| {
+    pub async fn render(
+        &mut self,
+        cache: &Cache,
+        jobs: &JobManager,
+        config: &Config,
+        prompt: Option<&Prompt>,
+        path: &Path,
+    ) -> crossterm::Result<()> {
+        let mut lines: Vec<String> = jobs
+            .iter()
+            .map(|job| {
+                let status = if job.is_cancelled() {
+                    "cancelling"
+                } else {
+                    "x to cancel"
+                };
+                format!("{} {} [{}]", job.task, job.progress, status)
+            })
+            .collect();
+        match prompt {
+            Some(prompt) => lines.push(format!("{}{}", prompt.kind.prefix(), prompt.buffer)),
+            // Command messages carry carriage returns which would break the pane
+            None => lines.push(self.command.to_string().replace('\r', "")),
+        }
+        let command = lines.join("\n");
+        // One line per job plus the last command, inside the borders
+        let command_height = lines.len() as u16 + 2;
+
+        let Self {
+            terminal,
+            offsets,
+            ls_colors,
+            owners,
+            previewer,
+            free_space,
+            ..
+        } = self;
+        let theme = &config.theme;
+
+        terminal.draw(|frame| {
             let vertical_chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .margin(1)
-                .constraints(constraints![95, 5])
+                .constraints(
+                    [
+                        Constraint::Min(0),
+                        Constraint::Length(1),
+                        Constraint::Length(command_height),
+                    ]
+                    .as_ref(),
+                )
                 .split(frame.size());
 
             let horizontal_chunks = Layout::default()
@@ -71,35 +133,125 @@ impl Painter {
                 .constraints(constraints![30, 40, 30])
                 .split(vertical_chunks[0]);
 
-            // 0'th because all vertical chunk heights are the same
-            // let general_chunk_height = vertical_chunks[0].height as usize - 3;
+            let default_block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(theme.border);
 
-            let default_block = Block::default().borders(Borders::ALL);
+            // The current directory can be missing for a moment if it was
+            // removed, until we move somewhere which still exists
+            let directory = cache.get(path);
 
-            let directory = cache.get(path).unwrap();
-            let title = &directory.path;
-            let body = directory.to_string();
+            let parent_block = default_block.clone().title("[ Parent ]");
+            match path.parent().and_then(|parent| cache.get(parent)) {
+                Some(parent) => {
+                    let parent_block = parent_block.title(title("Parent", parent));
+                    let height = parent_block.inner(horizontal_chunks[0]).height as usize;
+                    let offset = scroll(parent, 0, height);
+                    frame.render_stateful_widget(
+                        directory_list(parent.window(offset, height), None, theme, ls_colors)
+                            .block(parent_block),
+                        horizontal_chunks[0],
+                        &mut list_state(parent, offset),
+                    );
+                }
+                None => frame.render_widget(parent_block, horizontal_chunks[0]),
+            }
 
-            frame.render_widget(
-                default_block.clone().title("[ Parent ]"),
-                horizontal_chunks[0],
-            );
-            frame.render_widget(
-                Paragraph::new(body).block(
-                    default_block
+            match directory {
+                Some(directory) => {
+                    let name = match &directory.filter {
+                        Some(filter) => format!(
+                            "{} (filter: {})",
+                            directory.path.to_string_lossy(),
+                            filter.pattern
+                        ),
+                        None => directory.path.to_string_lossy().to_string(),
+                    };
+                    let current_block = default_block.clone().title(title(&name, directory));
+                    let inner = current_block.inner(horizontal_chunks[1]);
+                    let height = inner.height as usize;
+                    let offset = offsets.entry(path.to_path_buf()).or_default();
+                    *offset = scroll(directory, *offset, height);
+                    let entries = directory.window(*offset, height);
+                    let lines =
+                        column_lines(entries, &config.columns, owners, inner.width as usize);
+                    frame.render_stateful_widget(
+                        directory_list(entries, lines, theme, ls_colors).block(current_block),
+                        horizontal_chunks[1],
+                        &mut list_state(directory, *offset),
+                    );
+                }
+                None => {
+                    let current_block = default_block
                         .clone()
-                        .title(format!("[ {} ]", title.to_string_lossy().as_ref())),
-                ),
-                horizontal_chunks[1],
-            );
+                        .title(format!("[ {} ]", path.to_string_lossy()));
+                    frame.render_widget(
+                        Paragraph::new("cannot be read").block(current_block),
+                        horizontal_chunks[1],
+                    );
+                }
+            }
+
+            let preview_block = default_block.clone().title("[ Preview ]");
+            let preview_area = preview_block.inner(horizontal_chunks[2]);
+            match directory.and_then(Directory::selected) {
+                // Prefer the cached listing for directories
+                Some(entry) => match cache.get(&entry.path) {
+                    Some(child) => {
+                        let preview_block = preview_block.title(title("Preview", child));
+                        let height = preview_area.height as usize;
+                        let offset = scroll(child, 0, height);
+                        frame.render_stateful_widget(
+                            directory_list(child.window(offset, height), None, theme, ls_colors)
+                                .block(preview_block),
+                            horizontal_chunks[2],
+                            &mut list_state(child, offset),
+                        );
+                    }
+                    None => frame.render_widget(
+                        Paragraph::new(previewer.preview(
+                            entry,
+                            preview_area.width as usize,
+                            preview_area.height as usize,
+                            &config.preview,
+                        ))
+                        .block(preview_block),
+                        horizontal_chunks[2],
+                    ),
+                },
+                None => frame.render_widget(preview_block, horizontal_chunks[2]),
+            }
+
+            let status_area = vertical_chunks[1];
+            if let Some(directory) = directory {
+                let status = status_line(
+                    directory,
+                    &config.columns,
+                    owners,
+                    free_space.get(&directory.path),
+                    status_area.width as usize,
+                );
+                frame.render_widget(Paragraph::new(status).style(theme.status), status_area);
+            }
+
+            let command_block = default_block.clone().title("[ Command ]");
+            let command_area = command_block.inner(vertical_chunks[2]);
             frame.render_widget(
-                default_block.clone().title("[ Preview ]"),
-                horizontal_chunks[2],
+                Paragraph::new(command)
+                    .style(theme.command)
+                    .block(command_block),
+                vertical_chunks[2],
             );
-            frame.render_widget(
-                Paragraph::new(command).block(default_block.clone().title("[ Command ]")),
-                vertical_chunks[1],
-            )
+
+            // Place the cursor after the prompt's prefix on the last line
+            if let Some(prompt) = prompt {
+                let before: String = prompt.buffer.chars().take(prompt.cursor).collect();
+                let x = command_area.x + (prompt.kind.prefix().width() + before.width()) as u16;
+                frame.set_cursor(
+                    x.min(command_area.right().saturating_sub(1)),
+                    command_area.bottom().saturating_sub(1),
+                );
+            }
         })?;
         Ok(())
     }
@@ -111,6 +263,59 @@ impl Painter {
     }
 }
 
+// Lists the entries shown in a pane, with `lines` laid out by `column_lines`
+fn directory_list<'a>(
+    entries: &'a [Entry],
+    lines: Option<Vec<String>>,
+    theme: &Theme,
+    ls_colors: &LsColors,
+) -> List<'a> {
+    let items: Vec<ListItem> = match lines {
+        Some(lines) => entries
+            .iter()
+            .zip(lines)
+            .map(|(entry, line)| ListItem::new(line).style(theme.entry_style(entry, ls_colors)))
+            .collect(),
+        None => entries
+            .iter()
+            .map(|entry| {
+                ListItem::new(entry.label.as_str()).style(theme.entry_style(entry, ls_colors))
+            })
+            .collect(),
+    };
+
+    List::new(items).highlight_style(theme.selection)
+}
+
+// Pane titles note when a directory is still being read
+fn title(name: &str, directory: &Directory) -> String {
+    match directory.is_loading() {
+        true => format!("[ {} (loading…) ]", name),
+        false => format!("[ {} ]", name),
+    }
+}
+
+// Scrolls a pane `height` rows tall from `offset` just far enough to keep the
+// selection in view, as tui's lists would, so only what's shown is drawn
+fn scroll(directory: &Directory, offset: usize, height: usize) -> usize {
+    let selected = directory.index.unwrap_or(0);
+    let offset = offset.min(directory.len().saturating_sub(1));
+    if selected >= offset + height {
+        selected + 1 - height
+    } else if selected < offset {
+        selected
+    } else {
+        offset
+    }
+}
+
+// Selects within the window of entries starting at `offset`
+fn list_state(directory: &Directory, offset: usize) -> ListState {
+    let mut state = ListState::default();
+    state.select(directory.index.and_then(|i| i.checked_sub(offset)));
+    state
+}
+
 impl AsRef<Terminal> for Painter {
     fn as_ref(&self) -> &Terminal {
         &self.terminal
@@ -0,0 +1,91 @@
+use super::{column::Owners, Column};
+use crate::{
+    config::ColumnConfig,
+    fs::{util::human_size, Directory, LinkType},
+};
+
+use unicode_width::UnicodeWidthStr;
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// How long free space is shown before it's checked again
+const FREE_SPACE_INTERVAL: Duration = Duration::from_secs(2);
+
+const DETAILS: [Column; 5] = [
+    Column::Permissions,
+    Column::User,
+    Column::Group,
+    Column::Size,
+    Column::Modified,
+];
+
+// Summarizes the selected entry on the left, and the directory on the right
+pub fn status_line(
+    directory: &Directory,
+    config: &ColumnConfig,
+    owners: &Owners,
+    free: Option<u64>,
+    width: usize,
+) -> String {
+    let mut left = String::new();
+    if let Some(entry) = directory.selected() {
+        let details: Vec<String> = DETAILS
+            .iter()
+            .map(|column| column.cell(entry, config, owners))
+            // Owners are blank off unix, as are sizes of uncounted directories
+            .filter(|cell| !cell.is_empty())
+            .collect();
+        left = details.join(" ");
+        if let LinkType::Symlink(target) = &entry.metadata.link_type {
+            left += &format!(" -> {}", target);
+        }
+    }
+
+    let mut right = Vec::new();
+    let flagged = directory.iter().filter(|e| e.flagged).count();
+    if flagged > 0 {
+        right.push(format!("{} flagged", flagged));
+    }
+    let position = directory.index.map_or(0, |i| i + 1);
+    right.push(format!("{}/{}", position, directory.len()));
+    if let Some(free) = free {
+        right.push(format!("{} free", human_size(free)));
+    }
+    let right = right.join("  ");
+
+    // The right side is dropped before it would touch the left
+    let used = left.width() + right.width();
+    if used >= width {
+        return left;
+    }
+    format!("{}{}{}", left, " ".repeat(width - used), right)
+}
+
+// Free space on the current directory's filesystem. Asking for it means a
+// statvfs call, so it's only asked again when the directory changes or the
+// last answer is a little old
+pub struct FreeSpace {
+    checked: Option<(PathBuf, Instant, Option<u64>)>,
+}
+
+impl FreeSpace {
+    pub fn new() -> Self {
+        Self { checked: None }
+    }
+
+    pub fn get(&mut self, path: &Path) -> Option<u64> {
+        match &self.checked {
+            Some((checked, at, free)) if checked == path && at.elapsed() < FREE_SPACE_INTERVAL => {
+                *free
+            }
+            _ => {
+                let free = fs2::available_space(path).ok();
+                self.checked = Some((path.to_path_buf(), Instant::now(), free));
+                free
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use super::ls_colors::LsColors;
+use crate::fs::Entry;
+
+use serde::{de, Deserialize, Deserializer};
+use tui::style::{Color, Modifier, Style};
+
+use std::fmt::Display;
+
+// Styles are written as words, eg. "bold yellow" or "white on #1d2021".
+// The first color is the foreground, and one after "on" the background
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "deserialize_style")]
+    pub border: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub selection: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub directory: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub symlink: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub executable: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub flagged: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub status: Style,
+    #[serde(deserialize_with = "deserialize_style")]
+    pub command: Style,
+    // Color entries with the shell's LS_COLORS, when it is set
+    pub ls_colors: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            border: Style::default(),
+            selection: Style::default().add_modifier(Modifier::REVERSED),
+            directory: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            symlink: Style::default().fg(Color::Cyan),
+            executable: Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+            flagged: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            status: Style::default(),
+            command: Style::default(),
+            ls_colors: true,
+        }
+    }
+}
+
+impl Theme {
+    // Styles an entry by its type, preferring LS_COLORS, with flagged
+    // entries drawn over the top
+    pub fn entry_style(&self, entry: &Entry, ls_colors: &LsColors) -> Style {
+        let metadata = &entry.metadata;
+        let style = match self.ls_colors {
+            true => ls_colors.style(entry),
+            false => None,
+        }
+        .unwrap_or_else(|| {
+            if metadata.is_symlink() {
+                self.symlink
+            } else if metadata.is_dir() {
+                self.directory
+            } else if metadata.is_executable() {
+                self.executable
+            } else {
+                Style::default()
+            }
+        });
+
+        match entry.flagged {
+            true => style.patch(self.flagged),
+            false => style,
+        }
+    }
+}
+
+fn deserialize_style<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Style, D::Error> {
+    let spec = String::deserialize(deserializer)?;
+    parse_style(&spec).map_err(de::Error::custom)
+}
+
+pub fn parse_style(spec: &str) -> Result<Style, StyleError> {
+    let mut style = Style::default();
+    let mut background = false;
+    for word in spec.split_whitespace() {
+        let modifier = match word.to_lowercase().as_str() {
+            "on" => {
+                background = true;
+                continue;
+            }
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underline" | "underlined" => Modifier::UNDERLINED,
+            "blink" => Modifier::SLOW_BLINK,
+            "reverse" | "reversed" => Modifier::REVERSED,
+            "hidden" => Modifier::HIDDEN,
+            "crossed" | "strikethrough" => Modifier::CROSSED_OUT,
+            _ => {
+                let color = parse_color(word).ok_or_else(|| StyleError(word.to_string()))?;
+                style = match background {
+                    true => style.bg(color),
+                    false => style.fg(color),
+                };
+                continue;
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+// Accepts color names, "#rrggbb" and 256 color indices
+fn parse_color(word: &str) -> Option<Color> {
+    let color = match word.to_lowercase().as_str() {
+        "default" | "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.starts_with('#') && hex.len() == 7 && hex.is_ascii() => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            Color::Rgb(channel(1)?, channel(3)?, channel(5)?)
+        }
+        index => Color::Indexed(index.parse().ok()?),
+    };
+    Some(color)
+}
+
+#[derive(Debug)]
+pub struct StyleError(String);
+
+impl Display for StyleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown style \"{}\"", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors_and_modifiers() {
+        let style = parse_style("bold Yellow on blue").unwrap();
+        assert_eq!(
+            style,
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Yellow)
+                .bg(Color::Blue)
+        );
+        assert_eq!(parse_style("").unwrap(), Style::default());
+    }
+
+    #[test]
+    fn parses_hex_and_indexed_colors() {
+        let style = parse_style("#ff8000 on 236").unwrap();
+        assert_eq!(
+            style,
+            Style::default()
+                .fg(Color::Rgb(255, 128, 0))
+                .bg(Color::Indexed(236))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_words() {
+        for spec in ["purple", "#ff80", "#gg0000", "256"] {
+            assert!(parse_style(spec).is_err(), "{}", spec);
+        }
+        assert_eq!(
+            parse_style("shiny").unwrap_err().to_string(),
+            "unknown style \"shiny\""
+        );
+    }
+}